@@ -1,42 +1,44 @@
 pub mod day_1 {
 
-    pub fn input() -> Vec<u16> {
+    pub fn input() -> Vec<u32> {
         let input = include_str!("../input.txt");
         input
             .trim()
             .split('\n')
-            .map(|l| str::parse::<u16>(l).unwrap())
-            .collect::<Vec<u16>>()
+            .map(|l| str::parse::<u32>(l).unwrap())
+            .collect::<Vec<u32>>()
     }
 
-    pub fn part_1<T>(numbers: &mut T) -> u16
+    /// The number of times the sum of a window of `k` consecutive readings is bigger than the
+    /// sum of the window one before.
+    /// Consecutive windows share all but their end readings, so this compares each reading with
+    /// the one `k` before it, remembering only the last `k` readings.
+    pub fn count_window_increases<I>(numbers: I, k: usize) -> usize
     where
-        T: Iterator<Item = u16>,
+        I: IntoIterator<Item = u32>,
     {
+        assert!(k > 0, "Expected a nonempty window");
+        let mut numbers = numbers.into_iter();
+        // The last k readings, with the oldest at `oldest`.
+        let mut window: Vec<u32> = numbers.by_ref().take(k).collect();
+        let mut oldest = 0;
         let mut count = 0;
-        let mut previous = 0;
         for i in numbers {
-            if previous < i {
+            if window[oldest] < i {
                 count += 1;
             }
-            previous = i;
+            window[oldest] = i;
+            oldest = (oldest + 1) % k;
         }
-        count - 1
+        count
     }
 
-    pub fn part_2_naive(numbers: &[u16]) -> u16 {
-        part_1(&mut numbers.windows(3).map(|x| x[0] + x[1] + x[2]))
+    pub fn part_1(numbers: &[u32]) -> usize {
+        count_window_increases(numbers.iter().copied(), 1)
     }
 
-    pub fn part_2(numbers: &[u16]) -> u16 {
-        let mut count = 0;
-        for i in 3..numbers.len() {
-            if numbers[i - 3] < numbers[i] {
-                count += 1;
-            }
-        }
-
-        count
+    pub fn part_2(numbers: &[u32]) -> usize {
+        count_window_increases(numbers.iter().copied(), 3)
     }
 }
 
@@ -44,30 +46,48 @@ pub mod day_1 {
 mod tests {
     use super::day_1::*;
 
+    static TEST_INPUT: [u32; 10] = [199, 200, 208, 210, 200, 207, 240, 269, 260, 263];
+
     #[test]
     fn part1_known() {
-        assert_eq!(
-            part_1(
-                &mut [199, 200, 208, 210, 200, 207, 240, 269, 260, 263]
-                    .iter()
-                    .copied()
-            ),
-            7
-        );
+        assert_eq!(part_1(&TEST_INPUT), 7);
     }
 
     #[test]
     fn part2_known() {
+        assert_eq!(part_2(&TEST_INPUT), 5);
+    }
+
+    #[test]
+    fn window_increases() {
+        // A first reading of zero isn't an increase on anything.
+        assert_eq!(count_window_increases([0, 1, 0, 2], 1), 2);
+        assert_eq!(count_window_increases([5], 1), 0);
+        assert_eq!(count_window_increases([], 1), 0);
+        assert_eq!(count_window_increases([1, 2, 3], 3), 0);
+        assert_eq!(count_window_increases([1, 2, 3, 4], 3), 1);
+        assert_eq!(count_window_increases([u32::MAX; 5], 2), 0);
         assert_eq!(
-            part_2(&[199, 200, 208, 210, 200, 207, 240, 269, 260, 263]),
-            5
+            count_window_increases([u32::MAX - 1, u32::MAX, u32::MAX, u32::MAX], 2),
+            1
         );
+
+        // Against summing every window.
+        let input = input();
+        for k in 1..=10 {
+            let sums: Vec<u64> = input
+                .windows(k)
+                .map(|w| w.iter().map(|&i| i as u64).sum())
+                .collect();
+            let expected = sums.windows(2).filter(|w| w[0] < w[1]).count();
+            assert_eq!(count_window_increases(input.iter().copied(), k), expected);
+        }
     }
 
     #[test]
     fn test_day_1() {
         let input = input();
-        assert_eq!(part_1(&mut input.iter().copied()), 1766);
+        assert_eq!(part_1(&input), 1766);
         assert_eq!(part_2(&input), 1797);
     }
 }
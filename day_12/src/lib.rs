@@ -1,11 +1,12 @@
 pub mod day_12 {
 
     use std::collections::{HashMap, HashSet};
+    use std::fmt;
 
     #[derive(Debug)]
     pub struct Cave<'a> {
         is_big: bool,
-        _name: &'a str,
+        name: &'a str,
     }
 
     #[derive(Debug)]
@@ -13,8 +14,8 @@ pub mod day_12 {
         caves: Vec<Cave<'a>>,
         /// The 0th element is the set of caves you can hit from caves[0].
         edges: Vec<HashSet<u16>>,
-        start: u16,
-        end: u16,
+        start: Option<u16>,
+        end: Option<u16>,
     }
 
     fn insert_cave<'a>(
@@ -27,15 +28,12 @@ pub mod day_12 {
                 let is_big = name.chars().next().unwrap().is_uppercase();
                 let i = caves.caves.len() as u16;
                 mapping.insert(name, i);
-                caves.caves.push(Cave {
-                    is_big,
-                    _name: name,
-                });
+                caves.caves.push(Cave { is_big, name });
                 caves.edges.push(HashSet::new());
-                if caves.start == 0 && name == "start" {
-                    caves.start = i;
-                } else if caves.end == 0 && name == "end" {
-                    caves.end = i;
+                if name == "start" {
+                    caves.start = Some(i);
+                } else if name == "end" {
+                    caves.end = Some(i);
                 }
                 i
             }
@@ -44,13 +42,18 @@ pub mod day_12 {
         index
     }
 
-    pub(crate) fn parse(s: &str) -> CaveSystem {
-        let mut answer = CaveSystem {
+    fn empty<'a>() -> CaveSystem<'a> {
+        CaveSystem {
             caves: Vec::new(),
             edges: Vec::new(),
-            start: 0,
-            end: 0,
-        };
+            start: None,
+            end: None,
+        }
+    }
+
+    /// Parse the puzzle's format: one `a-b` edge per line.
+    pub fn parse(s: &str) -> CaveSystem<'_> {
+        let mut answer = empty();
         let mut mapping: HashMap<&str, u16> = HashMap::new();
         for line in s.trim().split('\n') {
             let mut iter = line.split('-');
@@ -78,22 +81,135 @@ pub mod day_12 {
         answer
     }
 
+    /// Parse an adjacency list: one `cave: neighbour neighbour ...` entry per line.
+    /// Edges are undirected, so each one need only be listed under one of its endpoints;
+    /// a cave with no neighbours may be declared as `cave:`.
+    pub fn parse_adjacency(s: &str) -> CaveSystem<'_> {
+        let mut answer = empty();
+        let mut mapping: HashMap<&str, u16> = HashMap::new();
+        for line in s.trim().split('\n') {
+            let (name, neighbours) = match line.split_once(':') {
+                None => {
+                    panic!("Expected 'cave: neighbours', got {}", line);
+                }
+                Some(pair) => pair,
+            };
+            let name = name.trim();
+            if name.is_empty() {
+                panic!("Expected a cave name before ':', got {}", line);
+            }
+            let index = insert_cave(&mut answer, &mut mapping, name);
+            for neighbour in neighbours.split_whitespace() {
+                let neighbour_index = insert_cave(&mut answer, &mut mapping, neighbour);
+                answer.edges[index as usize].insert(neighbour_index);
+                answer.edges[neighbour_index as usize].insert(index);
+            }
+        }
+        answer
+    }
+
+    // A cave's name as a DOT quoted string, with quotes and backslashes escaped.
+    fn dot_id(name: &str) -> String {
+        let mut result = String::with_capacity(name.len() + 2);
+        result.push('"');
+        for c in name.chars() {
+            if c == '"' || c == '\\' {
+                result.push('\\');
+            }
+            result.push(c);
+        }
+        result.push('"');
+        result
+    }
+
+    impl<'a> CaveSystem<'a> {
+        /// Every edge exactly once, as a pair of names in lexicographic order;
+        /// the list is sorted, so it does not depend on the order caves were discovered.
+        fn sorted_edges(&self) -> Vec<(&'a str, &'a str)> {
+            let mut result: Vec<_> = self
+                .edges
+                .iter()
+                .enumerate()
+                .flat_map(|(i, neighbours)| {
+                    neighbours
+                        .iter()
+                        .filter(move |&&j| i <= j as usize)
+                        .map(move |&j| {
+                            let a = self.caves[i].name;
+                            let b = self.caves[j as usize].name;
+                            if a <= b {
+                                (a, b)
+                            } else {
+                                (b, a)
+                            }
+                        })
+                })
+                .collect();
+            result.sort_unstable();
+            result
+        }
+
+        /// Render as a Graphviz DOT undirected graph.
+        /// Big caves are drawn as boxes, small caves as ellipses, and start and end are filled in.
+        pub fn to_dot(&self) -> String {
+            let mut result = String::from("graph caves {\n");
+            for (i, cave) in self.caves.iter().enumerate() {
+                let i = i as u16;
+                let shape = if cave.is_big { "box" } else { "ellipse" };
+                let fill = if Some(i) == self.start {
+                    ", style=filled, fillcolor=palegreen"
+                } else if Some(i) == self.end {
+                    ", style=filled, fillcolor=lightcoral"
+                } else {
+                    ""
+                };
+                result.push_str(&format!(
+                    "    {} [shape={}{}];\n",
+                    dot_id(cave.name),
+                    shape,
+                    fill
+                ));
+            }
+            for (a, b) in self.sorted_edges() {
+                result.push_str(&format!("    {} -- {};\n", dot_id(a), dot_id(b)));
+            }
+            result.push_str("}\n");
+            result
+        }
+    }
+
+    /// Writes the puzzle's `a-b` format, one edge per line in sorted order, which `parse` reads back.
+    /// Caves with no edges can't be expressed in that format, so they are omitted.
+    impl<'a> fmt::Display for CaveSystem<'a> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            for (a, b) in self.sorted_edges() {
+                writeln!(f, "{}-{}", a, b)?;
+            }
+            fmt::Result::Ok(())
+        }
+    }
+
     pub fn input() -> CaveSystem<'static> {
         parse(include_str!("../input.txt"))
     }
 
+    /// With no start cave, there are no paths.
     pub fn part_1(data: &CaveSystem) -> u32 {
+        let start = match data.start {
+            None => return 0,
+            Some(start) => start,
+        };
         let mut paths_count = 0;
 
         let mut visited: Vec<u8> = (0..data.caves.len()).map(|_| 0).collect();
-        visited[data.start as usize] = 1;
-        let mut stack: Vec<_> = data.edges[data.start as usize]
+        visited[start as usize] = 1;
+        let mut stack: Vec<_> = data.edges[start as usize]
             .iter()
             .map(|i| (*i, visited.to_vec()))
             .collect();
 
         while let Some((current_node, visited)) = stack.pop() {
-            if current_node == data.end {
+            if Some(current_node) == data.end {
                 paths_count += 1;
             }
             let mut visited = visited.to_vec();
@@ -110,20 +226,25 @@ pub mod day_12 {
         paths_count
     }
 
+    /// With no start cave, there are no paths.
     pub fn part_2(data: &CaveSystem) -> u32 {
+        let start = match data.start {
+            None => return 0,
+            Some(start) => start,
+        };
         let mut paths_count = 0;
 
         let mut visited: Vec<u8> = (0..data.caves.len()).map(|_| 0).collect();
         // Record that we can't visit start again
-        visited[data.start as usize] = 2;
-        let mut stack: Vec<_> = data.edges[data.start as usize]
+        visited[start as usize] = 2;
+        let mut stack: Vec<_> = data.edges[start as usize]
             .iter()
             .map(|i| (*i, visited.to_vec(), false))
             .collect();
 
         while let Some((current_node, visited, small_visited_twice)) = stack.pop() {
             // We can only visit the end once.
-            if current_node == data.end {
+            if Some(current_node) == data.end {
                 paths_count += 1;
             } else {
                 stack.extend(
@@ -231,6 +352,77 @@ start-RW";
         assert_eq!(part_2(&data), 3509);
     }
 
+    #[test]
+    fn display_round_trips() {
+        for s in [TEST_INPUT_1, TEST_INPUT_2, TEST_INPUT_3] {
+            let data = parse(s);
+            let printed = data.to_string();
+            assert_eq!(printed.lines().count(), s.lines().count());
+            let reparsed = parse(&printed);
+            assert_eq!(reparsed.to_string(), printed);
+            assert_eq!(part_1(&reparsed), part_1(&data));
+            assert_eq!(part_2(&reparsed), part_2(&data));
+        }
+    }
+
+    #[test]
+    fn adjacency_known() {
+        let data = parse_adjacency(
+            "start: A b
+A: c b end
+b: d end
+c:
+d:
+end:",
+        );
+        assert_eq!(data.to_string(), parse(TEST_INPUT_1).to_string());
+        assert_eq!(part_1(&data), 10);
+        assert_eq!(part_2(&data), 36);
+    }
+
+    #[test]
+    fn dot_known() {
+        let data = parse("start-A\nA-end\nA-b");
+        assert_eq!(
+            data.to_dot(),
+            r#"graph caves {
+    "start" [shape=ellipse, style=filled, fillcolor=palegreen];
+    "A" [shape=box];
+    "end" [shape=ellipse, style=filled, fillcolor=lightcoral];
+    "b" [shape=ellipse];
+    "A" -- "b";
+    "A" -- "end";
+    "A" -- "start";
+}
+"#
+        );
+    }
+
+    #[test]
+    fn dot_escapes() {
+        // The first cave isn't start or end, so nothing is filled in.
+        let data = parse(r#"a"b-C\d"#);
+        assert_eq!(
+            data.to_dot(),
+            r#"graph caves {
+    "a\"b" [shape=ellipse];
+    "C\\d" [shape=box];
+    "C\\d" -- "a\"b";
+}
+"#
+        );
+    }
+
+    #[test]
+    fn missing_start_or_end() {
+        let data = parse("a-b\nb-end");
+        assert_eq!(part_1(&data), 0);
+        assert_eq!(part_2(&data), 0);
+        let data = parse("start-a\na-b");
+        assert_eq!(part_1(&data), 0);
+        assert_eq!(part_2(&data), 0);
+    }
+
     #[test]
     fn test_day_12() {
         let input = input();
@@ -1,5 +1,6 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use day_14::day_14::{input, part_1, part_2};
+use day_14::day_14::{input, part_1, part_2, Arithmetic};
+use std::num::NonZeroU64;
 
 fn criterion_benchmark(c: &mut Criterion) {
     let input = input();
@@ -13,6 +14,14 @@ fn criterion_benchmark(c: &mut Criterion) {
             black_box(part_2(&input));
         })
     });
+    c.bench_function("day 14 10000 steps, modular", |b| {
+        b.iter(|| {
+            black_box(input.element_counts_pow(
+                10000,
+                Arithmetic::Modulo(NonZeroU64::new(1_000_000_007).unwrap()),
+            ));
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -1,14 +1,21 @@
 pub mod day_14 {
 
-    use std::collections::HashMap;
+    pub use ::matrix::matrix::Arithmetic;
+    use ::matrix::matrix::Matrix;
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
 
     pub struct Data {
-        start: char,
-        end: char,
-        rules: HashMap<(char, char), char>,
+        /// Every element mentioned in the template or the rules; elements are referred to by
+        /// their index in here. The pair (a, b) has index `a * alphabet.len() + b`.
+        alphabet: Vec<char>,
+        template: Vec<usize>,
+        /// For each pair, the one or two pairs it becomes in a single step.
+        transitions: Vec<(usize, Option<usize>)>,
+        /// How many times each pair appears in the template.
+        pairs: Vec<u64>,
     }
 
-    pub(crate) fn parse(s: &str) -> (Data, HashMap<(char, char), u64>) {
+    pub fn parse(s: &str) -> Data {
         let mut lines = s.split('\n');
         let start = lines.next().unwrap();
         match lines.next().unwrap() {
@@ -16,7 +23,7 @@ pub mod day_14 {
             s => panic!("Expected empty line, got {}", s),
         }
 
-        let mut map = HashMap::new();
+        let mut rules = Vec::new();
         for line in lines {
             let mut iter = line.split(' ');
             let pair = iter.next().unwrap();
@@ -44,69 +51,116 @@ pub mod day_14 {
                 None => {}
                 Some(_) => panic!("Expected single char, got {}", middle),
             }
-            map.insert((p1, p2), insert);
+            rules.push((p1, p2, insert));
         }
 
-        let mut pairs = HashMap::new();
-        let mut iter = start.chars();
-        let start = iter.next().unwrap();
+        let alphabet: Vec<char> = start
+            .chars()
+            .chain(rules.iter().flat_map(|&(p1, p2, insert)| [p1, p2, insert]))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        let index: HashMap<char, usize> =
+            alphabet.iter().enumerate().map(|(i, &c)| (c, i)).collect();
+        let n = alphabet.len();
 
-        let mut prev = start;
+        let mut transitions: Vec<(usize, Option<usize>)> = (0..n * n).map(|p| (p, None)).collect();
+        for (p1, p2, insert) in rules {
+            let (p1, p2, insert) = (index[&p1], index[&p2], index[&insert]);
+            transitions[p1 * n + p2] = (p1 * n + insert, Some(insert * n + p2));
+        }
 
-        for next in iter {
-            pairs
-                .entry((prev, next))
-                .and_modify(|x| *x += 1)
-                .or_insert(1);
-            prev = next;
-        }
-
-        (
-            Data {
-                start,
-                end: prev,
-                rules: map,
-            },
+        let template: Vec<usize> = start.chars().map(|c| index[&c]).collect();
+        let mut pairs = vec![0; n * n];
+        for window in template.windows(2) {
+            pairs[window[0] * n + window[1]] += 1;
+        }
+
+        Data {
+            alphabet,
+            template,
+            transitions,
             pairs,
-        )
+        }
     }
 
-    pub fn input() -> (Data, HashMap<(char, char), u64>) {
+    pub fn input() -> Data {
         parse(include_str!("../input.txt"))
     }
 
-    fn run(
-        data: &Data,
-        pairs: &HashMap<(char, char), u64>,
-        steps: u8,
-    ) -> HashMap<(char, char), u64> {
-        let mut pairs = pairs.clone();
-        for _ in 0..steps {
-            let mut after_step = HashMap::new();
-            for (&(c1, c2), &count) in pairs.iter() {
-                match data.rules.get(&(c1, c2)) {
-                    None => {
-                        after_step
-                            .entry((c1, c2))
-                            .and_modify(|x| *x += count)
-                            .or_insert(count);
-                    }
-                    Some(&middle) => {
-                        after_step
-                            .entry((c1, middle))
-                            .and_modify(|x| *x += count)
-                            .or_insert(count);
-                        after_step
-                            .entry((middle, c2))
-                            .and_modify(|x| *x += count)
-                            .or_insert(count);
+    impl Data {
+        /// The last element of the template, which is the only one not at the start of a pair.
+        fn end(&self) -> usize {
+            *self.template.last().unwrap()
+        }
+
+        /// The pair counts after the given number of steps, one step at a time.
+        fn run(&self, steps: u64) -> Vec<u64> {
+            let mut pairs = self.pairs.clone();
+            let mut after_step: Vec<u64> = vec![0; pairs.len()];
+            for _ in 0..steps {
+                after_step.iter_mut().for_each(|x| *x = 0);
+                for (&count, &(first, second)) in pairs.iter().zip(self.transitions.iter()) {
+                    after_step[first] = after_step[first]
+                        .checked_add(count)
+                        .expect("Pair counts overflowed a u64; use element_counts_pow");
+                    if let Some(second) = second {
+                        after_step[second] = after_step[second]
+                            .checked_add(count)
+                            .expect("Pair counts overflowed a u64; use element_counts_pow");
                     }
-                };
+                }
+                std::mem::swap(&mut pairs, &mut after_step);
             }
-            pairs = after_step;
+
+            pairs
         }
 
-        pairs
+        /// The single-step transition matrix: entry (q, p) is how many q-pairs one p-pair becomes.
+        fn transition_matrix(&self) -> Matrix {
+            let size = self.transitions.len();
+            let mut elts = vec![0; size * size];
+            for (p, &(first, second)) in self.transitions.iter().enumerate() {
+                elts[first * size + p] += 1;
+                if let Some(second) = second {
+                    elts[second * size + p] += 1;
+                }
+            }
+            Matrix::make(size, elts)
+        }
+
+        /// The pair counts after the given number of steps, via `Matrix::apply_pow` on the
+        /// transition matrix.
+        /// Returns `None` if the counts overflow under `Arithmetic::Checked`.
+        fn run_pow(&self, steps: u64, arithmetic: Arithmetic) -> Option<Vec<u128>> {
+            let pairs: Vec<u128> = self.pairs.iter().map(|&x| x as u128).collect();
+            self.transition_matrix()
+                .apply_pow(&pairs, steps, arithmetic)
+        }
+
+        /// How many of each element there are after the given number of steps, computed with
+        /// `Arithmetic` via `Matrix::apply_pow`. Returns `None` if the counts overflow.
+        pub fn element_counts_pow(
+            &self,
+            steps: u64,
+            arithmetic: Arithmetic,
+        ) -> Option<BTreeMap<char, u128>> {
+            let pairs = self.run_pow(steps, arithmetic)?;
+            let n = self.alphabet.len();
+            let mut counts = vec![0; n];
+            counts[self.end()] = 1;
+            for (p, &count) in pairs.iter().enumerate() {
+                counts[p / n] = arithmetic.add(counts[p / n], count)?;
+            }
+            Some(
+                self.alphabet
+                    .iter()
+                    .cloned()
+                    .zip(counts)
+                    .filter(|&(_, count)| count > 0)
+                    .collect(),
+            )
+        }
     }
 
     fn min_max<I, T, U>(mut iter: I, f: fn(T) -> U) -> Option<(T, T)>
@@ -132,46 +186,79 @@ pub mod day_14 {
         Some((min_entry, max_entry))
     }
 
-    fn count(map: &HashMap<(char, char), u64>, start: char, end: char) -> HashMap<char, u64> {
-        let mut counts = HashMap::new();
-        for (&(c1, c2), &count) in map.iter() {
-            counts
-                .entry(c1)
-                .and_modify(|i| *i += count)
-                .or_insert(count);
-            counts
-                .entry(c2)
-                .and_modify(|i| *i += count)
-                .or_insert(count);
-        }
-        *counts.get_mut(&start).unwrap() += 1;
-        *counts.get_mut(&end).unwrap() += 1;
-
-        counts
-    }
+    impl Data {
+        /// How many of each element there are after the given number of steps.
+        /// Panics if a count overflows a `u64`, which takes around 60 steps;
+        /// `element_counts_pow` can go further.
+        pub fn element_counts(&self, steps: u64) -> BTreeMap<char, u64> {
+            let n = self.alphabet.len();
+            let mut counts: Vec<u64> = vec![0; n];
+            // Every element but the last is the first of exactly one pair.
+            counts[self.end()] = 1;
+            for (p, count) in self.run(steps).into_iter().enumerate() {
+                counts[p / n] = counts[p / n]
+                    .checked_add(count)
+                    .expect("Element counts overflowed a u64; use element_counts_pow");
+            }
+            self.alphabet
+                .iter()
+                .cloned()
+                .zip(counts)
+                .filter(|&(_, count)| count > 0)
+                .collect()
+        }
 
-    pub fn part_1(data: &(Data, HashMap<(char, char), u64>)) -> u64 {
-        let map = run(&data.0, &data.1, 10);
-        let counts = count(&map, data.0.start, data.0.end);
-        match min_max(counts.iter(), |x| x.1) {
-            Some((min, max)) => max.1 / 2 - min.1 / 2,
-            None => panic!("Expected a nonempty map"),
+        /// The length of the polymer after the given number of steps, or `None` if it doesn't
+        /// fit in a `u128`.
+        pub fn polymer_len(&self, steps: u64) -> Option<u128> {
+            let pairs = self.run_pow(steps, Arithmetic::Checked)?;
+            pairs
+                .into_iter()
+                .try_fold(1u128, |total, count| total.checked_add(count))
+        }
+
+        /// The polymer itself after the given number of steps. Its length roughly doubles with
+        /// every step, so this is only feasible for small `steps`.
+        pub fn polymer(&self, steps: u32) -> String {
+            let n = self.alphabet.len();
+            let mut polymer = self.template.clone();
+            for _ in 0..steps {
+                let mut next = Vec::with_capacity(2 * polymer.len());
+                for window in polymer.windows(2) {
+                    next.push(window[0]);
+                    let pair = window[0] * n + window[1];
+                    if let (first, Some(_)) = self.transitions[pair] {
+                        next.push(first % n);
+                    }
+                }
+                next.push(self.end());
+                polymer = next;
+            }
+            polymer.into_iter().map(|i| self.alphabet[i]).collect()
         }
     }
 
-    pub fn part_2(data: &(Data, HashMap<(char, char), u64>)) -> u64 {
-        let map = run(&data.0, &data.1, 40);
-        let counts = count(&map, data.0.start, data.0.end);
-        match min_max(counts.iter(), |x| x.1) {
-            Some((min, max)) => max.1 / 2 - min.1 / 2,
-            None => panic!("Expected a nonempty map"),
+    fn most_minus_least(data: &Data, steps: u64) -> u64 {
+        let counts = data.element_counts(steps);
+        match min_max(counts.values(), |x| *x) {
+            Some((min, max)) => max - min,
+            None => panic!("Expected a nonempty polymer"),
         }
     }
+
+    pub fn part_1(data: &Data) -> u64 {
+        most_minus_least(data, 10)
+    }
+
+    pub fn part_2(data: &Data) -> u64 {
+        most_minus_least(data, 40)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::day_14::*;
+    use std::num::NonZeroU64;
 
     static TEST_INPUT: &str = "NNCB
 
@@ -204,6 +291,60 @@ CN -> C";
         assert_eq!(part_2(&data), 2188189693529);
     }
 
+    #[test]
+    fn polymer_known() {
+        let data = parse(TEST_INPUT);
+        let expected = [
+            "NNCB",
+            "NCNBCHB",
+            "NBCCNBBBCBHCB",
+            "NBBBCNCCNBBNBNBBCHBHHBCHB",
+            "NBBNBNBBCCNBCNCCNBBNBBNBBBNBBNBBCBHCBHHNHCBBCBHCB",
+        ];
+        for (steps, &polymer) in expected.iter().enumerate() {
+            assert_eq!(data.polymer(steps as u32), polymer);
+            assert_eq!(data.polymer_len(steps as u64), Some(polymer.len() as u128));
+        }
+        assert_eq!(data.polymer_len(5), Some(97));
+        assert_eq!(data.polymer_len(10), Some(3073));
+
+        let counts = data.element_counts(10);
+        let expected = [('B', 1749), ('C', 298), ('H', 161), ('N', 865)];
+        assert_eq!(counts, expected.into_iter().collect());
+
+        let polymer = data.polymer(10);
+        for (c, count) in counts {
+            assert_eq!(polymer.chars().filter(|&x| x == c).count() as u64, count);
+        }
+    }
+
+    #[test]
+    fn element_counts_pow_known() {
+        let data = parse(TEST_INPUT);
+        let counts = data.element_counts_pow(10, Arithmetic::Checked).unwrap();
+        let expected = [('B', 1749), ('C', 298), ('H', 161), ('N', 865)];
+        assert_eq!(counts, expected.into_iter().collect());
+
+        let counts = data.element_counts_pow(40, Arithmetic::Checked).unwrap();
+        assert_eq!(counts[&'B'] - counts[&'H'], 2188189693529);
+
+        // Roughly doubling every step, u128 runs out somewhere past 120 steps.
+        assert!(data.element_counts_pow(120, Arithmetic::Checked).is_some());
+        assert!(data.element_counts_pow(130, Arithmetic::Checked).is_none());
+
+        let modulus = NonZeroU64::new(1_000_000_007).unwrap();
+        let exact = data.element_counts_pow(100, Arithmetic::Checked).unwrap();
+        let modular = data
+            .element_counts_pow(100, Arithmetic::Modulo(modulus))
+            .unwrap();
+        for (c, count) in exact {
+            assert_eq!(modular[&c], count % modulus.get() as u128);
+        }
+        assert!(data
+            .element_counts_pow(10_000, Arithmetic::Modulo(modulus))
+            .is_some());
+    }
+
     #[test]
     fn test_day_14() {
         let input = input();
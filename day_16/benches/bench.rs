@@ -1,5 +1,8 @@
+use ::day_16::day_16::{self, input, parse, part_1, part_2, PacketStream, Padding};
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use day_16::day_16::{input, part_1, part_2};
+
+// Reaches the library through `crate::day_16`, as it does from the library's own tests.
+mod unpacked;
 
 fn criterion_benchmark(c: &mut Criterion) {
     let input = input();
@@ -13,6 +16,34 @@ fn criterion_benchmark(c: &mut Criterion) {
             black_box(part_2(&input));
         })
     });
+
+    let s = include_str!("../input.txt");
+    let bits = unpacked::parse(s);
+    c.bench_function("day 16 part 1, one byte per bit", |b| {
+        b.iter(|| {
+            black_box(unpacked::part_1(&bits));
+        })
+    });
+    c.bench_function("day 16 part 2, one byte per bit", |b| {
+        b.iter(|| {
+            black_box(unpacked::part_2(&bits));
+        })
+    });
+    c.bench_function("day 16 parse", |b| {
+        b.iter(|| {
+            black_box(parse(s));
+        })
+    });
+    c.bench_function("day 16 parse, one byte per bit", |b| {
+        b.iter(|| {
+            black_box(unpacked::parse(s));
+        })
+    });
+    c.bench_function("day 16 stream", |b| {
+        b.iter(|| {
+            black_box(PacketStream::new(s.as_bytes(), Padding::Trailing).count());
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,134 @@
+//! The original representation, which expands every bit of the transmission into its own
+//! byte. Kept as a baseline for benchmarking `BitReader`, and shared with the library's tests
+//! to check the two agree.
+
+use crate::day_16::{Expr, LengthType, LiteralPacket, OperatorPacket, Packet};
+
+fn consume_literal(bits: &[u8], places: u8) -> u32 {
+    let mut answer = 0;
+    for &bit in bits.iter().take(places as usize) {
+        answer *= 2;
+        answer += bit as u32;
+    }
+    answer
+}
+
+pub fn parse(s: &str) -> Vec<u8> {
+    let s = s.trim_end();
+    let mut answer = Vec::with_capacity(s.len() * 4);
+
+    for c in s.chars() {
+        let number = if c.is_ascii_digit() {
+            c as u8 - b'0'
+        } else {
+            (c as u8 - b'A') + 10
+        };
+        answer.push(number / 8);
+        answer.push((number / 4) % 2);
+        answer.push((number / 2) % 2);
+        answer.push(number % 2);
+    }
+
+    answer
+}
+
+fn chomp_literal(bits: &[u8]) -> (u8, &[u8], bool) {
+    (
+        consume_literal(&bits[1..=4], 4) as u8,
+        &bits[5..],
+        bits[0] == 1,
+    )
+}
+
+fn parse_packet(bits: &[u8]) -> (Packet, &[u8]) {
+    let version = 4 * bits[0] + 2 * bits[1] + bits[2];
+    let type_id = 4 * bits[3] + 2 * bits[4] + bits[5];
+    match type_id {
+        4 => {
+            let mut value = 0;
+            let mut should_continue = true;
+            let mut bits = &bits[6..];
+            while should_continue {
+                let (byte, bits_2, should_continue_2) = chomp_literal(bits);
+                should_continue &= should_continue_2;
+                bits = bits_2;
+                value = value * 16 + byte as u64;
+            }
+            (Packet::Literal(LiteralPacket { version, value }), bits)
+        }
+        _ => match bits[6] {
+            0 => {
+                let length = consume_literal(&bits[7..], 15);
+                let unparsed = &bits[7 + 15 + (length as usize)..];
+                let to_parse = &bits[7 + 15..7 + 15 + length as usize];
+                let mut sub_packets = Vec::new();
+                let mut to_parse = to_parse;
+                // The length is exact, so there's no padding to skip here.
+                while !to_parse.is_empty() {
+                    let (packet, next_bits) = parse_packet(to_parse);
+                    to_parse = next_bits;
+                    sub_packets.push(packet);
+                }
+                (
+                    Packet::Operator(OperatorPacket {
+                        version,
+                        type_id,
+                        length_type: LengthType::TotalBits,
+                        sub_packets,
+                    }),
+                    unparsed,
+                )
+            }
+            1 => {
+                let packets = consume_literal(&bits[7..], 11);
+                let mut sub_packets = Vec::with_capacity(packets as usize);
+                let mut bits = &bits[7 + 11..];
+                for _ in 0..packets {
+                    let (packet, next_bits) = parse_packet(bits);
+                    bits = next_bits;
+                    sub_packets.push(packet);
+                }
+                (
+                    Packet::Operator(OperatorPacket {
+                        version,
+                        type_id,
+                        length_type: LengthType::PacketCount,
+                        sub_packets,
+                    }),
+                    bits,
+                )
+            }
+            c => panic!("Expected a bit, got {}", c),
+        },
+    }
+}
+
+fn parse_packets(bits: &[u8]) -> Vec<Packet> {
+    let mut bits = bits;
+    let mut answer = Vec::new();
+    while bits.contains(&1) {
+        let (packet, unparsed) = parse_packet(bits);
+        answer.push(packet);
+        bits = unparsed;
+    }
+
+    answer
+}
+
+fn sum_versions(p: &Packet) -> u32 {
+    match p {
+        Packet::Literal(p) => p.version as u32,
+        Packet::Operator(p) => {
+            p.sub_packets.iter().map(sum_versions).sum::<u32>() + p.version as u32
+        }
+    }
+}
+
+pub fn part_1(data: &[u8]) -> u32 {
+    sum_versions(&parse_packets(data)[0])
+}
+
+pub fn part_2(data: &[u8]) -> u128 {
+    let packet = &parse_packets(data)[0];
+    Expr::try_from(packet).unwrap().evaluate().unwrap()
+}
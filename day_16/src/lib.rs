@@ -1,121 +1,561 @@
 pub mod day_16 {
 
-    pub(crate) fn parse(s: &str) -> Vec<u8> {
-        let s = s.trim_end();
-        let mut answer = Vec::with_capacity(s.len() * 4);
+    use std::fmt;
+    use std::io::{self, BufRead, BufReader, Read};
 
-        for c in s.chars() {
-            let number = if ('0'..='9').contains(&c) {
-                c as u8 - b'0'
-            } else {
-                (c as u8 - b'A') + 10
-            };
-            answer.push(number / 8);
-            answer.push((number / 4) % 2);
-            answer.push((number / 2) % 2);
-            answer.push(number % 2);
+    fn hex_digit(c: char) -> u8 {
+        match c.to_digit(16) {
+            None => panic!("Expected a hex digit, got {}", c),
+            Some(d) => d as u8,
         }
+    }
 
-        answer
+    /// A hex transmission, packed two digits to the byte.
+    pub struct Transmission {
+        bytes: Vec<u8>,
+        len: usize,
     }
 
-    struct OperatorPacket {
-        version: u8,
-        type_id: u8,
-        sub_packets: Vec<Packet>,
+    impl Transmission {
+        pub fn reader(&self) -> BitReader<'_> {
+            BitReader {
+                bytes: &self.bytes,
+                position: 0,
+                end: self.len,
+            }
+        }
     }
 
-    struct LiteralPacket {
-        version: u8,
-        value: u64,
+    pub fn parse(s: &str) -> Transmission {
+        let s = s.trim_end();
+        let mut bytes = Vec::with_capacity(s.len().div_ceil(2));
+        let mut chars = s.chars();
+        while let Some(high) = chars.next() {
+            let low = chars.next().map(hex_digit).unwrap_or(0);
+            bytes.push(hex_digit(high) * 16 + low);
+        }
+        Transmission {
+            bytes,
+            len: s.len() * 4,
+        }
     }
 
-    enum Packet {
-        Literal(LiteralPacket),
-        Operator(OperatorPacket),
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum DecodeError {
+        /// Tried to read `wanted` bits at `position`, but only `available` remained.
+        Truncated {
+            position: usize,
+            wanted: usize,
+            available: usize,
+        },
+        /// The literal starting at `position` doesn't fit in a `u64`.
+        LiteralTooLarge { position: usize },
+        /// An operator's sub-packets were meant to end at bit `expected`, but the last one ended at `actual`.
+        LengthMismatch { expected: usize, actual: usize },
+    }
+
+    /// A source of big-endian bit fields which the packet decoder can read from.
+    pub trait BitSource {
+        type Error: From<DecodeError>;
+
+        /// Read the next `places` bits (at most 64) as a big-endian number.
+        fn read_bits(&mut self, places: u8) -> Result<u64, Self::Error>;
+
+        /// The number of bits consumed so far.
+        fn position(&self) -> usize;
+
+        /// How many bits are left, if that's known without reading them.
+        fn known_remaining(&self) -> Option<usize> {
+            None
+        }
+    }
+
+    /// Reads big-endian bit fields out of packed bytes, up to (but excluding) bit `end`.
+    pub struct BitReader<'a> {
+        bytes: &'a [u8],
+        position: usize,
+        end: usize,
     }
 
-    fn consume_literal(bits: &[u8], places: u8) -> u32 {
-        let mut answer = 0;
-        for &bit in bits.iter().take(places as usize) {
-            answer *= 2;
-            answer += bit as u32;
+    impl<'a> BitReader<'a> {
+        pub fn remaining(&self) -> usize {
+            self.end - self.position
+        }
+
+        /// Where the trailing zeros begin: just after the last 1 bit, or the current position if
+        /// there is none. Scans back from the end, so only the padding itself is looked at.
+        fn padding_start(&self) -> usize {
+            (self.position..self.end)
+                .rev()
+                .find(|&i| self.bytes[i / 8] & (0x80 >> (i % 8)) != 0)
+                .map_or(self.position, |i| i + 1)
+        }
+    }
+
+    impl<'a> BitSource for BitReader<'a> {
+        type Error = DecodeError;
+
+        fn read_bits(&mut self, places: u8) -> Result<u64, DecodeError> {
+            assert!(places <= 64, "Can't read {} bits into a u64", places);
+            let mut places = places as usize;
+            if self.remaining() < places {
+                return Err(DecodeError::Truncated {
+                    position: self.position,
+                    wanted: places,
+                    available: self.remaining(),
+                });
+            }
+            let mut answer = 0;
+            while places > 0 {
+                let available = 8 - self.position % 8;
+                let taken = std::cmp::min(available, places);
+                let byte = self.bytes[self.position / 8] >> (available - taken);
+                answer = (answer << taken) | (byte & ((1u16 << taken) - 1) as u8) as u64;
+                self.position += taken;
+                places -= taken;
+            }
+            Ok(answer)
+        }
+
+        /// The number of bits consumed from the start of the underlying bytes.
+        fn position(&self) -> usize {
+            self.position
         }
-        answer
+
+        fn known_remaining(&self) -> Option<usize> {
+            Some(self.remaining())
+        }
+    }
+
+    /// How an operator packet declares the extent of its sub-packets.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum LengthType {
+        /// Length type ID 0: a 15-bit count of the bits the sub-packets occupy.
+        TotalBits,
+        /// Length type ID 1: an 11-bit count of the sub-packets.
+        PacketCount,
     }
 
-    fn chomp_literal(bits: &[u8]) -> (u8, &[u8], bool) {
-        (
-            consume_literal(&bits[1..=4], 4) as u8,
-            &bits[5..],
-            bits[0] == 1,
-        )
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct OperatorPacket {
+        pub version: u8,
+        pub type_id: u8,
+        pub length_type: LengthType,
+        pub sub_packets: Vec<Packet>,
     }
 
-    fn parse_packet(bits: &[u8]) -> (Packet, &[u8]) {
-        let version = 4 * bits[0] + 2 * bits[1] + bits[2];
-        let type_id = 4 * bits[3] + 2 * bits[4] + bits[5];
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct LiteralPacket {
+        pub version: u8,
+        pub value: u64,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Packet {
+        Literal(LiteralPacket),
+        Operator(OperatorPacket),
+    }
+
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum EncodeError {
+        /// Versions occupy three bits.
+        VersionTooLarge(u8),
+        /// Type IDs occupy three bits, and 4 is reserved for literals.
+        InvalidTypeId(u8),
+        /// A `TotalBits` operator's sub-packets took more than 15 bits to describe.
+        SubPacketsTooLong(usize),
+        /// A `PacketCount` operator had more than 11 bits' worth of sub-packets.
+        TooManySubPackets(usize),
+    }
+
+    fn parse_packet<B: BitSource>(reader: &mut B) -> Result<Packet, B::Error> {
+        let version = reader.read_bits(3)? as u8;
+        let type_id = reader.read_bits(3)? as u8;
         match type_id {
             4 => {
-                let mut value = 0;
-                let mut should_continue = true;
-                let mut bits = &bits[6..];
-                while should_continue {
-                    let (byte, bits_2, should_continue_2) = chomp_literal(bits);
-                    should_continue &= should_continue_2;
-                    bits = bits_2;
-                    value = value * 16 + byte as u64;
-                }
-                (Packet::Literal(LiteralPacket { version, value }), bits)
-            }
-            _ => match bits[6] {
+                let position = reader.position();
+                let mut value: u64 = 0;
+                loop {
+                    let should_continue = reader.read_bits(1)? == 1;
+                    if value.leading_zeros() < 4 {
+                        return Err(DecodeError::LiteralTooLarge { position }.into());
+                    }
+                    value = value * 16 + reader.read_bits(4)?;
+                    if !should_continue {
+                        break;
+                    }
+                }
+                Ok(Packet::Literal(LiteralPacket { version, value }))
+            }
+            _ => match reader.read_bits(1)? {
                 0 => {
-                    let length = consume_literal(&bits[7..], 15);
-                    let unparsed = &bits[7 + 15 + (length as usize)..];
-                    let to_parse = &bits[7 + 15..7 + 15 + length as usize];
-                    let sub_packets = parse_packets(to_parse);
-                    (
-                        Packet::Operator(OperatorPacket {
-                            version,
-                            type_id,
-                            sub_packets,
-                        }),
-                        unparsed,
-                    )
-                }
-                1 => {
-                    let packets = consume_literal(&bits[7..], 11);
+                    let length = reader.read_bits(15)? as usize;
+                    let position = reader.position();
+                    // Where the length of the source is known, sub-packets that can't fit are
+                    // reported before trying to read them.
+                    if let Some(available) = reader.known_remaining() {
+                        if available < length {
+                            return Err(DecodeError::Truncated {
+                                position,
+                                wanted: length,
+                                available,
+                            }
+                            .into());
+                        }
+                    }
+                    let end = position + length;
+                    let mut sub_packets = Vec::new();
+                    // The length is exact, so there's no padding to skip here.
+                    while reader.position() < end {
+                        sub_packets.push(parse_packet(reader)?);
+                    }
+                    if reader.position() != end {
+                        return Err(DecodeError::LengthMismatch {
+                            expected: end,
+                            actual: reader.position(),
+                        }
+                        .into());
+                    }
+                    Ok(Packet::Operator(OperatorPacket {
+                        version,
+                        type_id,
+                        length_type: LengthType::TotalBits,
+                        sub_packets,
+                    }))
+                }
+                _ => {
+                    let packets = reader.read_bits(11)?;
                     let mut sub_packets = Vec::with_capacity(packets as usize);
-                    let mut bits = &bits[7 + 11..];
                     for _ in 0..packets {
-                        let (packet, next_bits) = parse_packet(bits);
-                        bits = next_bits;
-                        sub_packets.push(packet);
+                        sub_packets.push(parse_packet(reader)?);
                     }
-                    (
-                        Packet::Operator(OperatorPacket {
-                            version,
-                            type_id,
-                            sub_packets,
-                        }),
-                        bits,
-                    )
-                }
-                c => panic!("Expected a bit, got {}", c),
+                    Ok(Packet::Operator(OperatorPacket {
+                        version,
+                        type_id,
+                        length_type: LengthType::PacketCount,
+                        sub_packets,
+                    }))
+                }
             },
         }
     }
 
-    fn parse_packets(bits: &[u8]) -> Vec<Packet> {
-        let mut bits = bits;
+    fn parse_packets(data: &Transmission) -> Result<Vec<Packet>, DecodeError> {
+        let mut reader = data.reader();
+        let end = reader.padding_start();
         let mut answer = Vec::new();
-        while bits.contains(&1) {
-            let (packet, unparsed) = parse_packet(bits);
-            answer.push(packet);
-            bits = unparsed;
+        while reader.position() < end {
+            answer.push(parse_packet(&mut reader)?);
+        }
+
+        Ok(answer)
+    }
+
+    /// Decode the first packet of a hex transmission.
+    pub fn decode(s: &str) -> Result<Packet, DecodeError> {
+        parse_packet(&mut parse(s).reader())
+    }
+
+    #[derive(Debug)]
+    pub enum StreamError {
+        Io(io::Error),
+        /// A character that is neither a hex digit nor whitespace, which would have begun at bit `position`.
+        InvalidHexDigit {
+            position: usize,
+            digit: char,
+        },
+        /// A packet was followed by padding which wasn't all zeros, starting at bit `position`.
+        NonZeroPadding {
+            position: usize,
+        },
+        Decode(DecodeError),
+    }
+
+    impl From<io::Error> for StreamError {
+        fn from(e: io::Error) -> StreamError {
+            StreamError::Io(e)
+        }
+    }
+
+    impl From<DecodeError> for StreamError {
+        fn from(e: DecodeError) -> StreamError {
+            StreamError::Decode(e)
+        }
+    }
+
+    /// How packets are separated in a stream.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Padding {
+        /// Packets are back to back, and any zeros after the last one are ignored, as in the puzzle.
+        /// A packet consisting entirely of zero bits can't be told apart from padding.
+        Trailing,
+        /// Every packet is followed by zeros up to the next byte boundary, as `encode` produces.
+        ByteAligned,
+    }
+
+    /// Reads bits from hex digits as they arrive, skipping whitespace.
+    struct HexBits<R> {
+        input: BufReader<R>,
+        /// Bits read from the input but not yet consumed; only the low `buffered` bits are meaningful.
+        buffer: u128,
+        buffered: usize,
+        /// Zero bits consumed from the input while looking for the next packet, which precede `buffer`.
+        pending_zeros: usize,
+        position: usize,
+    }
+
+    impl<R: Read> HexBits<R> {
+        /// Buffer digits until at least `wanted` bits are available or the input runs out.
+        fn fill(&mut self, wanted: usize) -> Result<(), StreamError> {
+            while self.buffered < wanted {
+                let c = match self.input.fill_buf()?.first() {
+                    None => return Ok(()),
+                    Some(&c) => c,
+                };
+                self.input.consume(1);
+                if c.is_ascii_whitespace() {
+                    continue;
+                }
+                let digit = match (c as char).to_digit(16) {
+                    None => {
+                        return Err(StreamError::InvalidHexDigit {
+                            position: self.position + self.pending_zeros + self.buffered,
+                            digit: c as char,
+                        })
+                    }
+                    Some(d) => d as u128,
+                };
+                self.buffer = (self.buffer << 4) | digit;
+                self.buffered += 4;
+            }
+            Ok(())
+        }
+
+        /// Whether the input is exhausted, apart from zero bits. Those zeros are remembered as a
+        /// count, so they're still available if they turn out to be the start of another packet.
+        fn only_zeros_remain(&mut self) -> Result<bool, StreamError> {
+            loop {
+                self.fill(1)?;
+                if self.buffered == 0 {
+                    return Ok(true);
+                }
+                if self.buffer != 0 {
+                    return Ok(false);
+                }
+                self.pending_zeros += self.buffered;
+                self.buffered = 0;
+            }
+        }
+    }
+
+    impl<R: Read> BitSource for HexBits<R> {
+        type Error = StreamError;
+
+        fn read_bits(&mut self, places: u8) -> Result<u64, StreamError> {
+            assert!(places <= 64, "Can't read {} bits into a u64", places);
+            let places = places as usize;
+            let from_zeros = std::cmp::min(self.pending_zeros, places);
+            let from_buffer = places - from_zeros;
+            self.fill(from_buffer)?;
+            if self.buffered < from_buffer {
+                return Err(DecodeError::Truncated {
+                    position: self.position,
+                    wanted: places,
+                    available: self.pending_zeros + self.buffered,
+                }
+                .into());
+            }
+            self.pending_zeros -= from_zeros;
+            self.buffered -= from_buffer;
+            let answer = self.buffer >> self.buffered;
+            self.buffer &= (1 << self.buffered) - 1;
+            self.position += places;
+            Ok(answer as u64)
+        }
+
+        fn position(&self) -> usize {
+            self.position
+        }
+    }
+
+    /// Decodes packets one at a time from a hex transmission of any length, holding only the
+    /// packet currently being decoded in memory. Stops after the first error.
+    pub struct PacketStream<R> {
+        bits: HexBits<R>,
+        padding: Padding,
+        finished: bool,
+    }
+
+    impl<R: Read> PacketStream<R> {
+        pub fn new(input: R, padding: Padding) -> PacketStream<R> {
+            PacketStream {
+                bits: HexBits {
+                    input: BufReader::new(input),
+                    buffer: 0,
+                    buffered: 0,
+                    pending_zeros: 0,
+                    position: 0,
+                },
+                padding,
+                finished: false,
+            }
+        }
+
+        fn at_end(&mut self) -> Result<bool, StreamError> {
+            match self.padding {
+                Padding::Trailing => self.bits.only_zeros_remain(),
+                Padding::ByteAligned => {
+                    self.bits.fill(1)?;
+                    Ok(self.bits.buffered == 0)
+                }
+            }
+        }
+
+        fn next_packet(&mut self) -> Result<Packet, StreamError> {
+            let packet = parse_packet(&mut self.bits)?;
+            if self.padding == Padding::ByteAligned {
+                let position = self.bits.position;
+                let wanted = (8 - position % 8) % 8;
+                self.bits.fill(wanted)?;
+                // A transmission with an odd number of digits ends half way through a byte.
+                let padding = self
+                    .bits
+                    .read_bits(std::cmp::min(wanted, self.bits.buffered) as u8)?;
+                if padding != 0 {
+                    return Err(StreamError::NonZeroPadding { position });
+                }
+            }
+            Ok(packet)
+        }
+    }
+
+    impl<R: Read> Iterator for PacketStream<R> {
+        type Item = Result<Packet, StreamError>;
+
+        fn next(&mut self) -> Option<Result<Packet, StreamError>> {
+            if self.finished {
+                return None;
+            }
+            let result = match self.at_end() {
+                Ok(true) => {
+                    self.finished = true;
+                    return None;
+                }
+                Ok(false) => self.next_packet(),
+                Err(e) => Err(e),
+            };
+            self.finished = result.is_err();
+            Some(result)
         }
+    }
 
-        answer
+    /// Writes big-endian bit fields into packed bytes, the counterpart of `BitReader`.
+    /// Any bits after `len` in the last byte are zero.
+    struct BitWriter {
+        bytes: Vec<u8>,
+        len: usize,
+    }
+
+    impl BitWriter {
+        fn new() -> BitWriter {
+            BitWriter {
+                bytes: Vec::new(),
+                len: 0,
+            }
+        }
+
+        /// Write the low `places` bits (at most 64) of `value`, most significant first.
+        fn write_bits(&mut self, value: u64, places: u8) {
+            assert!(places <= 64, "Can't write {} bits from a u64", places);
+            let mut places = places as usize;
+            while places > 0 {
+                let available = 8 - self.len % 8;
+                if available == 8 {
+                    self.bytes.push(0);
+                }
+                let taken = std::cmp::min(available, places);
+                let chunk = (value >> (places - taken)) as u8 & ((1u16 << taken) - 1) as u8;
+                *self.bytes.last_mut().unwrap() |= chunk << (available - taken);
+                self.len += taken;
+                places -= taken;
+            }
+        }
+
+        /// Write every bit of `other` after the bits written so far.
+        fn append(&mut self, other: &BitWriter) {
+            let (whole, rest) = (other.len / 8, other.len % 8);
+            for &byte in &other.bytes[..whole] {
+                self.write_bits(byte as u64, 8);
+            }
+            if rest > 0 {
+                self.write_bits((other.bytes[whole] >> (8 - rest)) as u64, rest as u8);
+            }
+        }
+    }
+
+    fn encode_bits(p: &Packet, writer: &mut BitWriter) -> Result<(), EncodeError> {
+        match p {
+            Packet::Literal(p) => {
+                if p.version >= 8 {
+                    return Err(EncodeError::VersionTooLarge(p.version));
+                }
+                writer.write_bits(p.version as u64, 3);
+                writer.write_bits(4, 3);
+                let groups = std::cmp::max(1, (64 - p.value.leading_zeros()).div_ceil(4));
+                for group in (0..groups).rev() {
+                    writer.write_bits(u64::from(group > 0), 1);
+                    writer.write_bits((p.value >> (4 * group)) & 0xF, 4);
+                }
+            }
+            Packet::Operator(p) => {
+                if p.version >= 8 {
+                    return Err(EncodeError::VersionTooLarge(p.version));
+                }
+                if p.type_id >= 8 || p.type_id == 4 {
+                    return Err(EncodeError::InvalidTypeId(p.type_id));
+                }
+                writer.write_bits(p.version as u64, 3);
+                writer.write_bits(p.type_id as u64, 3);
+                match p.length_type {
+                    LengthType::TotalBits => {
+                        let mut body = BitWriter::new();
+                        for sub_packet in p.sub_packets.iter() {
+                            encode_bits(sub_packet, &mut body)?;
+                        }
+                        if body.len >= 1 << 15 {
+                            return Err(EncodeError::SubPacketsTooLong(body.len));
+                        }
+                        writer.write_bits(0, 1);
+                        writer.write_bits(body.len as u64, 15);
+                        writer.append(&body);
+                    }
+                    LengthType::PacketCount => {
+                        if p.sub_packets.len() >= 1 << 11 {
+                            return Err(EncodeError::TooManySubPackets(p.sub_packets.len()));
+                        }
+                        writer.write_bits(1, 1);
+                        writer.write_bits(p.sub_packets.len() as u64, 11);
+                        for sub_packet in p.sub_packets.iter() {
+                            encode_bits(sub_packet, writer)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Encode a packet as a hex transmission, zero-padded to a whole number of bytes.
+    /// Literals use the fewest groups that hold their value.
+    pub fn encode(p: &Packet) -> Result<String, EncodeError> {
+        let mut writer = BitWriter::new();
+        encode_bits(p, &mut writer)?;
+        // The writer only ever holds whole bytes, so the padding is already there.
+        Ok(writer
+            .bytes
+            .iter()
+            .map(|byte| format!("{:02X}", byte))
+            .collect())
     }
 
     fn sum_versions(p: &Packet) -> u32 {
@@ -127,59 +567,383 @@ pub mod day_16 {
         }
     }
 
-    fn evaluate(p: &Packet) -> u64 {
-        match p {
-            Packet::Literal(p) => p.value,
-            Packet::Operator(p) => match p.type_id {
-                0 => p.sub_packets.iter().map(evaluate).sum(),
-                1 => p.sub_packets.iter().map(evaluate).product(),
-                2 => p.sub_packets.iter().map(evaluate).min().unwrap(),
-                3 => p.sub_packets.iter().map(evaluate).max().unwrap(),
-                5 => {
-                    let v1 = evaluate(&p.sub_packets[0]);
-                    let v2 = evaluate(&p.sub_packets[1]);
-                    u64::from(v1 > v2)
-                }
-                6 => {
-                    let v1 = evaluate(&p.sub_packets[0]);
-                    let v2 = evaluate(&p.sub_packets[1]);
-                    u64::from(v1 < v2)
-                }
-                7 => {
-                    let v1 = evaluate(&p.sub_packets[0]);
-                    let v2 = evaluate(&p.sub_packets[1]);
-                    u64::from(v1 == v2)
-                }
-                _ => panic!("Unexpected type"),
-            },
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum ExprError {
+        /// An operator packet with a type ID that names no operation.
+        UnknownTypeId(u8),
+        /// A comparison operator without exactly two sub-packets.
+        WrongArity { type_id: u8, operands: usize },
+        /// A minimum or maximum of no sub-packets.
+        NoOperands { type_id: u8 },
+        /// The value didn't fit in a `u128`.
+        Overflow,
+    }
+
+    /// The packet tree, read as an arithmetic expression.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum Expr {
+        Literal(u64),
+        Sum(Vec<Expr>),
+        Product(Vec<Expr>),
+        Minimum(Vec<Expr>),
+        Maximum(Vec<Expr>),
+        GreaterThan(Box<Expr>, Box<Expr>),
+        LessThan(Box<Expr>, Box<Expr>),
+        EqualTo(Box<Expr>, Box<Expr>),
+    }
+
+    impl TryFrom<&Packet> for Expr {
+        type Error = ExprError;
+
+        fn try_from(p: &Packet) -> Result<Expr, ExprError> {
+            let p = match p {
+                Packet::Literal(p) => return Ok(Expr::Literal(p.value)),
+                Packet::Operator(p) => p,
+            };
+            let mut operands = p
+                .sub_packets
+                .iter()
+                .map(Expr::try_from)
+                .collect::<Result<Vec<_>, _>>()?;
+            match p.type_id {
+                0 => Ok(Expr::Sum(operands)),
+                1 => Ok(Expr::Product(operands)),
+                2 | 3 if operands.is_empty() => Err(ExprError::NoOperands { type_id: p.type_id }),
+                2 => Ok(Expr::Minimum(operands)),
+                3 => Ok(Expr::Maximum(operands)),
+                5..=7 => {
+                    if operands.len() != 2 {
+                        return Err(ExprError::WrongArity {
+                            type_id: p.type_id,
+                            operands: operands.len(),
+                        });
+                    }
+                    let v2 = Box::new(operands.pop().unwrap());
+                    let v1 = Box::new(operands.pop().unwrap());
+                    Ok(match p.type_id {
+                        5 => Expr::GreaterThan(v1, v2),
+                        6 => Expr::LessThan(v1, v2),
+                        _ => Expr::EqualTo(v1, v2),
+                    })
+                }
+                type_id => Err(ExprError::UnknownTypeId(type_id)),
+            }
+        }
+    }
+
+    // The minimum or maximum (as `pick` chooses) of the operands, which can't be empty.
+    fn extreme(
+        operands: &[Expr],
+        type_id: u8,
+        pick: fn(u128, u128) -> u128,
+    ) -> Result<u128, ExprError> {
+        let mut values = operands.iter().map(Expr::evaluate);
+        let first = values.next().ok_or(ExprError::NoOperands { type_id })??;
+        values.try_fold(first, |acc, v| Ok(pick(acc, v?)))
+    }
+
+    impl Expr {
+        pub fn evaluate(&self) -> Result<u128, ExprError> {
+            match self {
+                Expr::Literal(value) => Ok(*value as u128),
+                Expr::Sum(operands) => operands.iter().try_fold(0u128, |total, e| {
+                    total.checked_add(e.evaluate()?).ok_or(ExprError::Overflow)
+                }),
+                Expr::Product(operands) => operands.iter().try_fold(1u128, |total, e| {
+                    total.checked_mul(e.evaluate()?).ok_or(ExprError::Overflow)
+                }),
+                Expr::Minimum(operands) => extreme(operands, 2, std::cmp::min),
+                Expr::Maximum(operands) => extreme(operands, 3, std::cmp::max),
+                Expr::GreaterThan(v1, v2) => Ok(u128::from(v1.evaluate()? > v2.evaluate()?)),
+                Expr::LessThan(v1, v2) => Ok(u128::from(v1.evaluate()? < v2.evaluate()?)),
+                Expr::EqualTo(v1, v2) => Ok(u128::from(v1.evaluate()? == v2.evaluate()?)),
+            }
+        }
+
+        /// Whether this is printed as a single token or a function call, so needs no brackets.
+        fn is_atomic(&self) -> bool {
+            match self {
+                Expr::Literal(_) | Expr::Minimum(_) | Expr::Maximum(_) => true,
+                Expr::Sum(operands) | Expr::Product(operands) => operands.len() < 2,
+                _ => false,
+            }
+        }
+    }
+
+    fn fmt_operand(e: &Expr, f: &mut fmt::Formatter) -> fmt::Result {
+        if e.is_atomic() {
+            write!(f, "{}", e)
+        } else {
+            write!(f, "({})", e)
+        }
+    }
+
+    fn fmt_joined(operands: &[Expr], separator: &str, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, e) in operands.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{}", separator)?;
+            }
+            fmt_operand(e, f)?;
+        }
+        fmt::Result::Ok(())
+    }
+
+    /// Infix notation, with every compound operand bracketed: `(3 + (5 * 7)) < 12`.
+    /// Minimum and maximum, and sums and products of fewer than two operands, are written as calls.
+    impl fmt::Display for Expr {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            let (name, operands, separator) = match self {
+                Expr::Literal(value) => return write!(f, "{}", value),
+                Expr::GreaterThan(v1, v2) | Expr::LessThan(v1, v2) | Expr::EqualTo(v1, v2) => {
+                    fmt_operand(v1, f)?;
+                    match self {
+                        Expr::GreaterThan(_, _) => write!(f, " > ")?,
+                        Expr::LessThan(_, _) => write!(f, " < ")?,
+                        _ => write!(f, " == ")?,
+                    }
+                    return fmt_operand(v2, f);
+                }
+                Expr::Sum(operands) => ("sum", operands, " + "),
+                Expr::Product(operands) => ("product", operands, " * "),
+                Expr::Minimum(operands) => ("min", operands, ", "),
+                Expr::Maximum(operands) => ("max", operands, ", "),
+            };
+            if self.is_atomic() {
+                write!(f, "{}(", name)?;
+                fmt_joined(operands, ", ", f)?;
+                write!(f, ")")
+            } else {
+                fmt_joined(operands, separator, f)
+            }
         }
     }
 
-    pub fn input() -> Vec<u8> {
+    fn evaluate(p: &Packet) -> u128 {
+        Expr::try_from(p).unwrap().evaluate().unwrap()
+    }
+
+    pub fn input() -> Transmission {
         parse(include_str!("../input.txt"))
     }
 
-    pub fn part_1(data: &[u8]) -> u32 {
-        sum_versions(&parse_packets(data)[0])
+    pub fn part_1(data: &Transmission) -> u32 {
+        sum_versions(&parse_packets(data).unwrap()[0])
     }
 
-    pub fn part_2(data: &[u8]) -> u64 {
-        evaluate(&parse_packets(data)[0])
+    pub fn part_2(data: &Transmission) -> u128 {
+        evaluate(&parse_packets(data).unwrap()[0])
     }
 }
 
+// The one-byte-per-bit decoder the benchmarks compare against.
+#[cfg(test)]
+#[path = "../benches/unpacked/mod.rs"]
+mod unpacked;
+
 #[cfg(test)]
 mod tests {
     use super::day_16::*;
+    use super::unpacked;
+    use proptest::prelude::*;
+
+    fn arb_packet() -> impl Strategy<Value = Packet> {
+        let leaf = (0..8u8, any::<u64>())
+            .prop_map(|(version, value)| Packet::Literal(LiteralPacket { version, value }));
+        leaf.prop_recursive(4, 64, 5, |inner| {
+            (
+                0..8u8,
+                prop_oneof![0..4u8, 5..8u8],
+                prop_oneof![Just(LengthType::TotalBits), Just(LengthType::PacketCount)],
+                prop::collection::vec(inner, 0..5),
+            )
+                .prop_map(|(version, type_id, length_type, sub_packets)| {
+                    Packet::Operator(OperatorPacket {
+                        version,
+                        type_id,
+                        length_type,
+                        sub_packets,
+                    })
+                })
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn encode_round_trips(p in arb_packet()) {
+            let encoded = encode(&p).unwrap();
+            prop_assert_eq!(decode(&encoded).unwrap(), p);
+        }
+    }
+
+    #[test]
+    fn encode_known() {
+        for s in [
+            "D2FE28",
+            "38006F45291200",
+            "EE00D40C823060",
+            "8A004A801A8002F478",
+            "620080001611562C8802118E34",
+            "C0015000016115A2E0802F182340",
+            "A0016C880162017C3686B18A3D4780",
+        ] {
+            assert_eq!(encode(&decode(s).unwrap()).unwrap(), s);
+        }
+        assert_eq!(
+            encode(&Packet::Literal(LiteralPacket {
+                version: 8,
+                value: 0
+            })),
+            Err(EncodeError::VersionTooLarge(8))
+        );
+        assert_eq!(
+            encode(&Packet::Operator(OperatorPacket {
+                version: 0,
+                type_id: 4,
+                length_type: LengthType::PacketCount,
+                sub_packets: vec![]
+            })),
+            Err(EncodeError::InvalidTypeId(4))
+        );
+    }
 
     #[test]
     fn test_parse() {
         assert_eq!(
-            parse("D2FE28"),
+            unpacked::parse("D2FE28"),
             [1, 1, 0, 1, 0, 0, 1, 0, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 1, 0, 1, 0, 0, 0]
         );
     }
 
+    #[test]
+    fn test_bit_reader() {
+        let data = parse("D2FE28");
+        let mut reader = data.reader();
+        assert_eq!(reader.read_bits(3), Ok(6));
+        assert_eq!(reader.read_bits(3), Ok(4));
+        assert_eq!(reader.read_bits(5), Ok(0b10111));
+        assert_eq!(reader.read_bits(13), Ok(0b1111000101000));
+        assert_eq!(reader.position(), 24);
+        assert_eq!(
+            reader.read_bits(1),
+            Err(DecodeError::Truncated {
+                position: 24,
+                wanted: 1,
+                available: 0
+            })
+        );
+
+        // An odd number of digits mustn't expose the padding nibble.
+        let data = parse("D2F");
+        let mut reader = data.reader();
+        assert_eq!(reader.read_bits(12), Ok(0xD2F));
+        assert_eq!(reader.remaining(), 0);
+    }
+
+    #[test]
+    fn decode_truncated() {
+        assert_eq!(
+            decode("D2FE2"),
+            Err(DecodeError::Truncated {
+                position: 17,
+                wanted: 4,
+                available: 3
+            })
+        );
+        // An operator claiming 27 bits of sub-packets, with only 26 present.
+        assert_eq!(
+            decode("38006F452912"),
+            Err(DecodeError::Truncated {
+                position: 22,
+                wanted: 27,
+                available: 26
+            })
+        );
+        // An operator claiming 26 bits of sub-packets, which actually take 27.
+        assert_eq!(
+            decode("38006B45291200"),
+            Err(DecodeError::LengthMismatch {
+                expected: 48,
+                actual: 49
+            })
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn stream_round_trips(ps in prop::collection::vec(arb_packet(), 0..5)) {
+            let encoded: String = ps.iter().map(|p| encode(p).unwrap()).collect();
+            let decoded = PacketStream::new(encoded.as_bytes(), Padding::ByteAligned)
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap();
+            prop_assert_eq!(decoded, ps);
+        }
+    }
+
+    #[test]
+    fn stream_known() {
+        let stream = PacketStream::new(include_str!("../input.txt").as_bytes(), Padding::Trailing);
+        let packets = stream.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(packets, [decode(include_str!("../input.txt")).unwrap()]);
+
+        // An all-zero operator with no sub-packets, then the literal 2021 with no padding between.
+        let all_zeros = Packet::Operator(OperatorPacket {
+            version: 0,
+            type_id: 0,
+            length_type: LengthType::TotalBits,
+            sub_packets: vec![],
+        });
+        let stream = PacketStream::new("0000034BF8A0\n".as_bytes(), Padding::Trailing);
+        let packets = stream.collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(packets, [all_zeros, decode("D2FE28").unwrap()]);
+
+        let mut stream = PacketStream::new("D2FE29".as_bytes(), Padding::ByteAligned);
+        assert!(matches!(
+            stream.next(),
+            Some(Err(StreamError::NonZeroPadding { position: 21 }))
+        ));
+        assert!(stream.next().is_none());
+
+        let mut stream = PacketStream::new("D2FE28D2FE".as_bytes(), Padding::ByteAligned);
+        assert!(matches!(stream.next(), Some(Ok(_))));
+        assert!(matches!(
+            stream.next(),
+            Some(Err(StreamError::Decode(DecodeError::Truncated {
+                position: 40,
+                wanted: 1,
+                available: 0
+            })))
+        ));
+
+        // A stream doesn't know how much is left, so only finds out part way through a
+        // sub-packet that they don't all fit.
+        let mut stream = PacketStream::new("38006F452912".as_bytes(), Padding::Trailing);
+        assert!(matches!(
+            stream.next(),
+            Some(Err(StreamError::Decode(DecodeError::Truncated {
+                position: 45,
+                wanted: 4,
+                available: 3
+            })))
+        ));
+
+        let mut stream = PacketStream::new("D2FX28".as_bytes(), Padding::Trailing);
+        assert!(matches!(
+            stream.next(),
+            Some(Err(StreamError::InvalidHexDigit {
+                position: 12,
+                digit: 'X'
+            }))
+        ));
+    }
+
+    #[test]
+    fn unpacked_agrees() {
+        let input = input();
+        let bits = unpacked::parse(include_str!("../input.txt"));
+        assert_eq!(unpacked::part_1(&bits), part_1(&input));
+        assert_eq!(unpacked::part_2(&bits), part_2(&input));
+    }
+
     #[test]
     fn part1_known() {
         // Version sums
@@ -219,6 +983,73 @@ mod tests {
         assert_eq!(part_2(&data), 1);
     }
 
+    #[test]
+    fn expr_display() {
+        let sum = Expr::Sum(vec![
+            Expr::Literal(3),
+            Expr::Product(vec![Expr::Literal(5), Expr::Literal(7)]),
+        ]);
+        let e = Expr::LessThan(Box::new(sum), Box::new(Expr::Literal(12)));
+        assert_eq!(e.to_string(), "(3 + (5 * 7)) < 12");
+        assert_eq!(e.evaluate(), Ok(0));
+
+        let e = Expr::try_from(&decode("9C0141080250320F1802104A08").unwrap()).unwrap();
+        assert_eq!(e.to_string(), "(1 + 3) == (2 * 2)");
+        let e = Expr::try_from(&decode("880086C3E88112").unwrap()).unwrap();
+        assert_eq!(e.to_string(), "min(7, 8, 9)");
+        let e = Expr::Sum(vec![Expr::Product(vec![Expr::Literal(4)])]);
+        assert_eq!(e.to_string(), "sum(product(4))");
+    }
+
+    #[test]
+    fn expr_errors() {
+        let operator = |type_id, sub_packets| {
+            Packet::Operator(OperatorPacket {
+                version: 0,
+                type_id,
+                length_type: LengthType::PacketCount,
+                sub_packets,
+            })
+        };
+        let literal = |value| Packet::Literal(LiteralPacket { version: 0, value });
+
+        assert_eq!(
+            Expr::try_from(&operator(4, vec![literal(1)])),
+            Err(ExprError::UnknownTypeId(4))
+        );
+        assert_eq!(
+            Expr::try_from(&operator(6, vec![literal(1)])),
+            Err(ExprError::WrongArity {
+                type_id: 6,
+                operands: 1
+            })
+        );
+        assert_eq!(
+            Expr::try_from(&operator(3, vec![])),
+            Err(ExprError::NoOperands { type_id: 3 })
+        );
+
+        // Built directly, these don't go through the check on packets.
+        assert_eq!(
+            Expr::Minimum(vec![]).evaluate(),
+            Err(ExprError::NoOperands { type_id: 2 })
+        );
+        assert_eq!(
+            Expr::Maximum(vec![]).evaluate(),
+            Err(ExprError::NoOperands { type_id: 3 })
+        );
+        let e = Expr::Maximum(vec![Expr::Literal(3), Expr::Minimum(vec![])]);
+        assert_eq!(e.evaluate(), Err(ExprError::NoOperands { type_id: 2 }));
+
+        let big = || literal(u64::MAX);
+        let e = Expr::try_from(&operator(1, vec![big(), big()])).unwrap();
+        assert_eq!(e.evaluate(), Ok(u64::MAX as u128 * u64::MAX as u128));
+        let e = Expr::try_from(&operator(1, vec![big(), big(), big()])).unwrap();
+        assert_eq!(e.evaluate(), Err(ExprError::Overflow));
+        let e = Expr::try_from(&operator(0, vec![big(), big()])).unwrap();
+        assert_eq!(e.evaluate(), Ok(2 * u64::MAX as u128));
+    }
+
     #[test]
     fn test_day_16() {
         let input = input();
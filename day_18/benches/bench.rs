@@ -13,6 +13,11 @@ fn criterion_benchmark(c: &mut Criterion) {
             black_box(part_2(&input));
         })
     });
+    c.bench_function("day 18 single addition", |b| {
+        b.iter(|| {
+            black_box(&input[0] + &input[1]);
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -1,359 +1,440 @@
 pub mod day_18 {
 
-    use std::cmp::max;
-
-    #[derive(Debug)]
-    pub enum PairEntry {
-        Number(u8),
-        Pair((usize, usize)),
+    use std::cmp::Reverse;
+    use std::iter::Peekable;
+    use std::ops::Add;
+    use std::str::{CharIndices, FromStr};
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    struct Leaf {
+        value: u32,
+        /// The number of pairs enclosing this regular number.
+        depth: u8,
     }
 
-    #[derive(Debug)]
-    pub struct Pair {
-        pairs: Vec<PairEntry>,
-        first: usize,
+    /// A snailfish number, stored as its regular numbers from left to right, each with its depth.
+    /// The sequence of depths determines the shape of the tree, so equality of the leaf lists is
+    /// equality of the trees.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct SnailfishNumber {
+        leaves: Vec<Leaf>,
     }
 
-    // It makes no sense to clone a PairEntry, so do it manually.
-    impl Clone for Pair {
-        fn clone(&self) -> Self {
-            let mut pairs = Vec::with_capacity(self.pairs.len());
-            for p in self.pairs.iter() {
-                match p {
-                    PairEntry::Number(i) => {
-                        pairs.push(PairEntry::Number(*i));
-                    }
-                    PairEntry::Pair(i) => {
-                        pairs.push(PairEntry::Pair(*i));
-                    }
-                }
-            }
-            Pair {
-                pairs,
-                first: self.first,
-            }
+    // Print the subtree of the given depth which starts at leaves[*index], and move past it.
+    fn fmt_inner(
+        leaves: &[Leaf],
+        index: &mut usize,
+        depth: u8,
+        f: &mut std::fmt::Formatter<'_>,
+    ) -> std::fmt::Result {
+        let leaf = leaves[*index];
+        if leaf.depth == depth {
+            *index += 1;
+            write!(f, "{}", leaf.value)
+        } else {
+            write!(f, "[")?;
+            fmt_inner(leaves, index, depth + 1, f)?;
+            write!(f, ",")?;
+            fmt_inner(leaves, index, depth + 1, f)?;
+            write!(f, "]")
         }
     }
 
-    pub struct PairIterator<'a> {
-        current_pos: Vec<(usize, u8, usize)>,
-        pairs: &'a [PairEntry],
-    }
-
-    #[derive(Debug)]
-    pub struct IterationEntry {
-        entry: usize,
-        value: u8,
-        depth: u8,
-        parent: usize,
-    }
-
-    impl<'a> Iterator for PairIterator<'a> {
-        type Item = IterationEntry;
-
-        fn next(&mut self) -> Option<Self::Item> {
-            let (top, depth, parent) = self.current_pos.pop()?;
-
-            match self.pairs[top] {
-                PairEntry::Number(x) => Some(IterationEntry {
-                    entry: top,
-                    value: x,
-                    depth,
-                    parent,
-                }),
-                PairEntry::Pair((left, right)) => {
-                    self.current_pos.push((right, depth + 1, top));
-                    self.current_pos.push((left, depth + 1, top));
-                    self.next()
-                }
-            }
+    impl std::fmt::Display for SnailfishNumber {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            fmt_inner(&self.leaves, &mut 0, 0, f)
         }
     }
 
-    impl PartialEq for Pair {
-        fn eq(&self, other: &Self) -> bool {
-            format!("{}", self) == format!("{}", other)
+    /// The sum of two snailfish numbers before reduction, or `None` if it would be nested more
+    /// than 255 pairs deep.
+    pub fn concatenate(p1: &SnailfishNumber, p2: &SnailfishNumber) -> Option<SnailfishNumber> {
+        let mut leaves = Vec::with_capacity(p1.leaves.len() + p2.leaves.len());
+        for leaf in p1.leaves.iter().chain(p2.leaves.iter()) {
+            leaves.push(Leaf {
+                value: leaf.value,
+                depth: leaf.depth.checked_add(1)?,
+            });
         }
+        Some(SnailfishNumber { leaves })
     }
 
-    impl Eq for Pair {}
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum ParseError {
+        /// `found` appeared at byte offset `position`, where it can't go.
+        UnexpectedChar {
+            position: usize,
+            found: char,
+        },
+        UnexpectedEnd,
+        /// The regular number starting at byte offset `position` doesn't fit in a `u32`.
+        NumberTooLarge {
+            position: usize,
+        },
+        /// The pair opened at byte offset `position` is nested more than 255 pairs deep.
+        TooDeep {
+            position: usize,
+        },
+    }
 
-    impl Pair {
-        fn iter(&self) -> PairIterator {
-            PairIterator {
-                current_pos: vec![(self.first, 0, 0)],
-                pairs: &self.pairs,
-            }
+    fn expect(chars: &mut Peekable<CharIndices>, expected: char) -> Result<(), ParseError> {
+        match chars.next() {
+            None => Err(ParseError::UnexpectedEnd),
+            Some((_, c)) if c == expected => Ok(()),
+            Some((position, found)) => Err(ParseError::UnexpectedChar { position, found }),
         }
     }
 
-    impl std::fmt::Display for Pair {
-        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-            let mut print_stack = vec![Ok(self.first)];
-            while let Some(to_print) = print_stack.pop() {
-                match to_print {
-                    Ok(index) => match self.pairs[index] {
-                        PairEntry::Number(n) => {
-                            write!(f, "{}", n)?;
-                        }
-                        PairEntry::Pair((p1, p2)) => {
-                            write!(f, "[")?;
-                            print_stack.push(Err(']'));
-                            print_stack.push(Ok(p2));
-                            print_stack.push(Err(','));
-                            print_stack.push(Ok(p1));
-                        }
-                    },
-                    Err(c) => {
-                        write!(f, "{}", c)?;
-                    }
+    fn parse_element(
+        chars: &mut Peekable<CharIndices>,
+        leaves: &mut Vec<Leaf>,
+        depth: u8,
+    ) -> Result<(), ParseError> {
+        match chars.next() {
+            None => Err(ParseError::UnexpectedEnd),
+            Some((position, '[')) => {
+                let depth = depth
+                    .checked_add(1)
+                    .ok_or(ParseError::TooDeep { position })?;
+                parse_element(chars, leaves, depth)?;
+                expect(chars, ',')?;
+                parse_element(chars, leaves, depth)?;
+                expect(chars, ']')
+            }
+            Some((position, c)) if c.is_ascii_digit() => {
+                let mut value = c.to_digit(10).unwrap();
+                while let Some(d) = chars.peek().and_then(|&(_, c)| c.to_digit(10)) {
+                    chars.next();
+                    value = value
+                        .checked_mul(10)
+                        .and_then(|v| v.checked_add(d))
+                        .ok_or(ParseError::NumberTooLarge { position })?;
                 }
+                leaves.push(Leaf { value, depth });
+                Ok(())
             }
-
-            Ok(())
+            Some((position, found)) => Err(ParseError::UnexpectedChar { position, found }),
         }
     }
 
-    pub(crate) fn concatenate(p1: &Pair, p2: &Pair) -> Pair {
-        let mut pairs = Vec::with_capacity(p1.pairs.len() + p2.pairs.len() + 1);
-        for entry in p1.pairs.iter() {
-            match entry {
-                PairEntry::Number(i) => {
-                    pairs.push(PairEntry::Number(*i));
-                }
-                PairEntry::Pair((fst, snd)) => {
-                    pairs.push(PairEntry::Pair((*fst, *snd)));
-                }
-            }
-        }
+    impl FromStr for SnailfishNumber {
+        type Err = ParseError;
 
-        for entry in p2.pairs.iter() {
-            match entry {
-                PairEntry::Number(i) => {
-                    pairs.push(PairEntry::Number(*i));
-                }
-                PairEntry::Pair((fst, snd)) => {
-                    pairs.push(PairEntry::Pair((
-                        *fst + p1.pairs.len(),
-                        *snd + p1.pairs.len(),
-                    )));
+        fn from_str(s: &str) -> Result<SnailfishNumber, ParseError> {
+            let mut chars = s.char_indices().peekable();
+            // A snailfish number is always a pair, never a bare regular number.
+            match chars.peek() {
+                Some((_, '[')) | None => {}
+                Some(&(position, found)) => {
+                    return Err(ParseError::UnexpectedChar { position, found });
                 }
             }
+            let mut leaves = Vec::new();
+            parse_element(&mut chars, &mut leaves, 0)?;
+            match chars.next() {
+                None => Ok(SnailfishNumber { leaves }),
+                Some((position, found)) => Err(ParseError::UnexpectedChar { position, found }),
+            }
         }
+    }
 
-        pairs.push(PairEntry::Pair((p1.first, p1.pairs.len() + p2.first)));
-        let first = pairs.len() - 1;
+    pub(crate) fn parse_line(s: &str) -> SnailfishNumber {
+        s.parse().unwrap()
+    }
 
-        Pair { pairs, first }
+    pub(crate) fn parse(s: &str) -> Vec<SnailfishNumber> {
+        s.split('\n').map(parse_line).collect()
     }
 
-    pub(crate) fn parse_line(s: &str) -> Pair {
-        let mut chars = s.chars();
+    pub fn input() -> Vec<SnailfishNumber> {
+        parse(include_str!("../input.txt"))
+    }
 
-        match chars.next() {
-            Some('[') => {}
-            Some(c) => {
-                panic!("Expected open '[', got {}", c);
-            }
-            None => {
-                panic!("Expected open '[', got nothing");
-            }
-        }
-        let mut open_stack = 1;
-        let mut pair_entries = Vec::new();
-        let mut unclaimed_entries = Vec::new();
-
-        while open_stack > 0 {
-            match chars.next().unwrap() {
-                ']' => {
-                    open_stack -= 1;
-                    let pair_1 = unclaimed_entries.pop().unwrap();
-                    let pair_2 = unclaimed_entries.pop().unwrap();
-                    pair_entries.push(PairEntry::Pair((pair_2, pair_1)));
-                    unclaimed_entries.push(pair_entries.len() - 1);
-                }
-                '[' => {
-                    open_stack += 1;
+    /// Perform every explosion, in a single left-to-right pass.
+    /// Rebuilds the tree bottom-up as it goes: `subtrees` holds the depth of each complete subtree
+    /// not yet joined to its sibling, and whether it's a single regular number. Two regular numbers
+    /// which join into a pair nested inside four others explode on the spot.
+    /// Returns `None` if a regular number overflows.
+    fn explode_all(p: &mut SnailfishNumber) -> Option<()> {
+        let mut leaves: Vec<Leaf> = Vec::with_capacity(p.leaves.len());
+        let mut subtrees: Vec<(u8, bool)> = Vec::with_capacity(p.leaves.len());
+        let mut carry = 0;
+        for &leaf in p.leaves.iter() {
+            leaves.push(Leaf {
+                value: leaf.value.checked_add(carry)?,
+                depth: leaf.depth,
+            });
+            carry = 0;
+            subtrees.push((leaf.depth, true));
+            while subtrees.len() >= 2 {
+                let (right_depth, right_is_leaf) = subtrees[subtrees.len() - 1];
+                let (left_depth, left_is_leaf) = subtrees[subtrees.len() - 2];
+                if left_depth != right_depth {
+                    break;
                 }
-                ',' => {}
-                c => {
-                    if ('0'..='9').contains(&c) {
-                        pair_entries.push(PairEntry::Number(c as u8 - b'0'));
-                        unclaimed_entries.push(pair_entries.len() - 1);
-                    } else {
-                        panic!("Unrecognised character: {}", c);
+                subtrees.truncate(subtrees.len() - 2);
+                if left_is_leaf && right_is_leaf && left_depth > 4 {
+                    let right = leaves.pop().unwrap();
+                    let left = leaves.pop().unwrap();
+                    if let Some(previous) = leaves.last_mut() {
+                        previous.value = previous.value.checked_add(left.value)?;
                     }
+                    carry = carry.checked_add(right.value)?;
+                    leaves.push(Leaf {
+                        value: 0,
+                        depth: left_depth - 1,
+                    });
+                    subtrees.push((left_depth - 1, true));
+                } else {
+                    subtrees.push((left_depth - 1, false));
                 }
             }
         }
-
-        let first = pair_entries.len() - 1;
-        Pair {
-            pairs: pair_entries,
-            first,
-        }
+        p.leaves = leaves;
+        Some(())
     }
 
-    pub(crate) fn parse(s: &str) -> Vec<Pair> {
-        s.split('\n').map(parse_line).collect()
+    /// Returns `None` if a regular number overflows, leaving `p` part way through reduction.
+    pub(crate) fn reduce(p: &mut SnailfishNumber) -> Option<()> {
+        explode_all(p)?;
+
+        // No pair is nested more than four deep now, and a split can only produce a pair
+        // one level deeper than the number it replaces. So a split at depth four must
+        // explode immediately, and nothing else can need to explode.
+        // Everything to the left of `i` is less than 10.
+        let mut i = 0;
+        while i < p.leaves.len() {
+            let Leaf { value, depth } = p.leaves[i];
+            if value < 10 {
+                i += 1;
+                continue;
+            }
+            let left = value / 2;
+            let right = value - left;
+            if depth < 4 {
+                let depth = depth + 1;
+                p.leaves[i] = Leaf { value: left, depth };
+                p.leaves.insert(
+                    i + 1,
+                    Leaf {
+                        value: right,
+                        depth,
+                    },
+                );
+            } else {
+                p.leaves[i].value = 0;
+                if let Some(next) = p.leaves.get_mut(i + 1) {
+                    next.value = next.value.checked_add(right)?;
+                }
+                if i > 0 {
+                    p.leaves[i - 1].value = p.leaves[i - 1].value.checked_add(left)?;
+                    i -= 1;
+                }
+            }
+        }
+        Some(())
     }
 
-    pub fn input() -> Vec<Pair> {
-        parse(include_str!("../input.txt"))
+    fn add(p1: &SnailfishNumber, p2: &SnailfishNumber) -> SnailfishNumber {
+        match p1.checked_add(p2) {
+            None => panic!("Overflowed adding {} and {}", p1, p2),
+            Some(sum) => sum,
+        }
     }
 
-    struct EitherSide {
-        left: Option<(usize, u8)>,
-        right: Option<(usize, u8)>,
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum ReductionAction {
+        /// The path from the root to the exploding pair, as a string of `L`s and `R`s.
+        Explode { path: String },
+        /// The regular number which was split.
+        Split { value: u32 },
     }
 
-    // entry is the first element of the pair
-    fn first_to_left_and_right(p: &Pair, entry: usize) -> EitherSide {
-        let mut iter = p.iter();
-        let mut prev = iter.next().unwrap();
-        let mut answer_left = None;
-        let mut ret = 0;
-
-        if prev.entry == entry {
-            ret = 1;
+    impl std::fmt::Display for ReductionAction {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                ReductionAction::Explode { path } => write!(f, "Explode at path {}", path),
+                ReductionAction::Split { value } => write!(f, "Split {}", value),
+            }
         }
+    }
 
-        for current in iter {
-            match ret {
-                2 => {
-                    return EitherSide {
-                        left: answer_left,
-                        right: Some((current.entry, current.value)),
-                    };
+    /// Perform the single action the puzzle's rules call for next, if any.
+    /// `reduce` gets to the same place much faster, but skips past the intermediate states.
+    /// Also returns `None` if a regular number overflows.
+    fn reduce_step(p: &mut SnailfishNumber) -> Option<ReductionAction> {
+        // The path to the current leaf, with `true` meaning right.
+        let mut path: Vec<bool> = Vec::new();
+        for i in 0..p.leaves.len() {
+            let Leaf { value, depth } = p.leaves[i];
+            path.resize(depth as usize, false);
+            let is_left = path.last() == Some(&false);
+            if depth > 4 && is_left && p.leaves.get(i + 1).map(|l| l.depth) == Some(depth) {
+                let right = p.leaves.remove(i + 1).value;
+                p.leaves[i] = Leaf {
+                    value: 0,
+                    depth: depth - 1,
+                };
+                if i > 0 {
+                    p.leaves[i - 1].value = p.leaves[i - 1].value.checked_add(value)?;
                 }
-                1 => {
-                    ret += 1;
+                if let Some(next) = p.leaves.get_mut(i + 1) {
+                    next.value = next.value.checked_add(right)?;
                 }
-                _ => {}
+                path.pop();
+                let path = path.iter().map(|&r| if r { 'R' } else { 'L' }).collect();
+                return Some(ReductionAction::Explode { path });
             }
-            if current.entry == entry {
-                answer_left = Some((prev.entry, prev.value));
-                ret = 1;
+            while path.last() == Some(&true) {
+                path.pop();
+            }
+            if let Some(last) = path.last_mut() {
+                *last = true;
             }
-
-            prev = current;
         }
 
-        EitherSide {
-            left: answer_left,
-            right: None,
-        }
+        let i = p.leaves.iter().position(|leaf| leaf.value >= 10)?;
+        let Leaf { value, depth } = p.leaves[i];
+        let left = value / 2;
+        let depth = depth + 1;
+        p.leaves[i] = Leaf { value: left, depth };
+        p.leaves.insert(
+            i + 1,
+            Leaf {
+                value: value - left,
+                depth,
+            },
+        );
+        Some(ReductionAction::Split { value })
     }
 
-    pub(crate) fn explode(p: &mut Pair, entry: usize) {
-        match p.pairs[entry] {
-            PairEntry::Number(_) => {
-                panic!("Expected a pair!");
-            }
-            PairEntry::Pair((left, right)) => {
-                let left_right = first_to_left_and_right(p, left);
-                match left_right.left {
-                    None => {}
-                    Some((left_entry, left_value)) => match p.pairs[left] {
-                        PairEntry::Pair(_) => panic!("Expected a left entry, got a pair"),
-                        PairEntry::Number(left) => {
-                            p.pairs[left_entry] = PairEntry::Number(left_value + left);
-                        }
-                    },
-                };
-                match left_right.right {
-                    None => {}
-                    Some((right_entry, right_value)) => match p.pairs[right] {
-                        PairEntry::Pair(_) => panic!("Expected a right entry, got a pair"),
-                        PairEntry::Number(right) => {
-                            p.pairs[right_entry] = PairEntry::Number(right_value + right);
-                        }
-                    },
-                };
-            }
-        };
-
-        p.pairs[entry] = PairEntry::Number(0);
+    /// Reduce one action at a time, recording each action along with the number it produced.
+    /// If a regular number overflows, the trace stops at the last state before the overflow.
+    pub fn reduce_traced(p: &SnailfishNumber) -> Vec<(ReductionAction, SnailfishNumber)> {
+        let mut current = p.clone();
+        let mut trace = Vec::new();
+        while let Some(action) = reduce_step(&mut current) {
+            trace.push((action, current.clone()));
+        }
+        trace
     }
 
-    pub(crate) fn split(p1: &mut Pair, entry: usize) {
-        match p1.pairs[entry] {
-            PairEntry::Pair(_) => {
-                panic!("Can't split a pair!");
-            }
-            PairEntry::Number(n) => {
-                let left = n / 2;
-                let right = n - left;
-                p1.pairs.push(PairEntry::Number(left));
-                p1.pairs.push(PairEntry::Number(right));
-                p1.pairs[entry] = PairEntry::Pair((p1.pairs.len() - 2, p1.pairs.len() - 1));
-            }
+    impl Add for SnailfishNumber {
+        type Output = SnailfishNumber;
+
+        fn add(self, rhs: SnailfishNumber) -> SnailfishNumber {
+            add(&self, &rhs)
         }
     }
 
-    pub(crate) fn reduce_step(p1: &mut Pair) -> bool {
-        for iteration in p1.iter() {
-            if iteration.depth > 4 {
-                explode(p1, iteration.parent);
-                return true;
-            }
-        }
+    impl<'a> Add<&'a SnailfishNumber> for &'a SnailfishNumber {
+        type Output = SnailfishNumber;
 
-        for iteration in p1.iter() {
-            if iteration.value >= 10 {
-                split(p1, iteration.entry);
-                return true;
-            }
+        fn add(self, rhs: &'a SnailfishNumber) -> SnailfishNumber {
+            add(self, rhs)
         }
+    }
 
-        false
+    fn sum_nonempty<I, T>(mut iter: I) -> SnailfishNumber
+    where
+        I: Iterator<Item = T>,
+        T: std::borrow::Borrow<SnailfishNumber>,
+    {
+        let fst = match iter.next() {
+            None => panic!("Snailfish addition has no identity, so an empty sum is undefined"),
+            Some(fst) => fst.borrow().clone(),
+        };
+        iter.fold(fst, |current, next| add(&current, next.borrow()))
     }
 
-    pub(crate) fn reduce(p: &mut Pair) {
-        while reduce_step(p) {}
+    /// Panics on an empty iterator: there is no snailfish zero.
+    impl std::iter::Sum for SnailfishNumber {
+        fn sum<I: Iterator<Item = SnailfishNumber>>(iter: I) -> SnailfishNumber {
+            sum_nonempty(iter)
+        }
     }
 
-    pub(crate) fn add(p1: &Pair, p2: &Pair) -> Pair {
-        let mut concatenated = concatenate(p1, p2);
-        reduce(&mut concatenated);
-        concatenated
+    /// Panics on an empty iterator: there is no snailfish zero.
+    impl<'a> std::iter::Sum<&'a SnailfishNumber> for SnailfishNumber {
+        fn sum<I: Iterator<Item = &'a SnailfishNumber>>(iter: I) -> SnailfishNumber {
+            sum_nonempty(iter)
+        }
     }
 
-    fn magnitude_inner(p: &Pair, entry: usize) -> u32 {
-        match p.pairs[entry] {
-            PairEntry::Pair((left, right)) => {
-                3 * magnitude_inner(p, left) + 2 * magnitude_inner(p, right)
+    impl SnailfishNumber {
+        /// The reduced sum, or `None` if it's nested too deep to represent or a regular number
+        /// overflows a `u32` during reduction.
+        pub fn checked_add(&self, rhs: &SnailfishNumber) -> Option<SnailfishNumber> {
+            let mut concatenated = concatenate(self, rhs)?;
+            reduce(&mut concatenated)?;
+            Some(concatenated)
+        }
+
+        /// The magnitude, or `None` if it doesn't fit in a `u64`.
+        pub fn checked_magnitude(&self) -> Option<u64> {
+            // Each stack entry is the magnitude of a complete subtree, with that subtree's depth.
+            let mut stack: Vec<(u64, u8)> = Vec::with_capacity(self.leaves.len());
+            for leaf in self.leaves.iter() {
+                let mut current = (leaf.value as u64, leaf.depth);
+                while let Some(&(left, depth)) = stack.last() {
+                    if depth != current.1 {
+                        break;
+                    }
+                    stack.pop();
+                    let magnitude = left
+                        .checked_mul(3)?
+                        .checked_add(current.0.checked_mul(2)?)?;
+                    current = (magnitude, depth - 1);
+                }
+                stack.push(current);
             }
-            PairEntry::Number(n) => n as u32,
+            Some(stack[0].0)
         }
-    }
 
-    pub(crate) fn magnitude(p: &Pair) -> u32 {
-        magnitude_inner(p, p.first)
+        pub fn magnitude(&self) -> u64 {
+            match self.checked_magnitude() {
+                None => panic!("Magnitude of {} overflowed", self),
+                Some(magnitude) => magnitude,
+            }
+        }
     }
 
-    pub(crate) fn final_sum(p1: &[Pair]) -> Pair {
-        let mut iter = p1.iter();
-        let fst = iter.next().unwrap();
+    pub fn part_1(data: &[SnailfishNumber]) -> u64 {
+        data.iter().sum::<SnailfishNumber>().magnitude()
+    }
 
-        iter.fold(fst.clone(), |current, next| add(&current, next))
+    // Ties are broken towards the lexicographically first (i, j), so the answer doesn't depend on
+    // the order in which candidates are compared.
+    fn pair_key(i: usize, j: usize, magnitude: u64) -> (u64, Reverse<(usize, usize)>) {
+        (magnitude, Reverse((i, j)))
     }
 
-    pub fn part_1(data: &[Pair]) -> u32 {
-        magnitude(&final_sum(data))
+    /// The largest magnitude of the sum of two different numbers from the list, as
+    /// `(magnitude, i, j)` where that sum is `data[i] + data[j]`.
+    #[cfg(not(feature = "parallel"))]
+    pub fn best_pair(data: &[SnailfishNumber]) -> Option<(u64, usize, usize)> {
+        (0..data.len())
+            .flat_map(|i| (0..data.len()).map(move |j| (i, j)))
+            .filter(|(i, j)| i != j)
+            .map(|(i, j)| ((&data[i] + &data[j]).magnitude(), i, j))
+            .max_by_key(|&(magnitude, i, j)| pair_key(i, j, magnitude))
     }
 
-    pub fn part_2(data: &[Pair]) -> u32 {
-        let mut best = 0;
-        for i in 0..data.len() {
-            for j in 0..data.len() {
-                if i != j {
-                    best = max(best, magnitude(&add(&data[i], &data[j])))
-                }
-            }
-        }
+    /// The largest magnitude of the sum of two different numbers from the list, as
+    /// `(magnitude, i, j)` where that sum is `data[i] + data[j]`.
+    #[cfg(feature = "parallel")]
+    pub fn best_pair(data: &[SnailfishNumber]) -> Option<(u64, usize, usize)> {
+        use rayon::prelude::*;
+        (0..data.len())
+            .into_par_iter()
+            .flat_map_iter(|i| (0..data.len()).map(move |j| (i, j)))
+            .filter(|(i, j)| i != j)
+            .map(|(i, j)| ((&data[i] + &data[j]).magnitude(), i, j))
+            .max_by_key(|&(magnitude, i, j)| pair_key(i, j, magnitude))
+    }
 
-        best
+    pub fn part_2(data: &[SnailfishNumber]) -> u64 {
+        best_pair(data).unwrap().0
     }
 }
 
@@ -413,55 +494,229 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            "[1,2".parse::<SnailfishNumber>(),
+            Err(ParseError::UnexpectedEnd)
+        );
+        assert_eq!(
+            "[1;2]".parse::<SnailfishNumber>(),
+            Err(ParseError::UnexpectedChar {
+                position: 2,
+                found: ';'
+            })
+        );
+        assert_eq!(
+            "[1,2]]".parse::<SnailfishNumber>(),
+            Err(ParseError::UnexpectedChar {
+                position: 5,
+                found: ']'
+            })
+        );
+        assert_eq!(
+            "7".parse::<SnailfishNumber>(),
+            Err(ParseError::UnexpectedChar {
+                position: 0,
+                found: '7'
+            })
+        );
+    }
+
+    #[test]
+    fn test_too_deep() {
+        let nested = |depth: usize| format!("{}1{}", "[".repeat(depth), ",0]".repeat(depth));
+        let deepest = nested(255);
+        assert!(deepest.parse::<SnailfishNumber>().is_ok());
+        assert_eq!(
+            nested(256).parse::<SnailfishNumber>(),
+            Err(ParseError::TooDeep { position: 255 })
+        );
+        assert_eq!(
+            nested(300).parse::<SnailfishNumber>(),
+            Err(ParseError::TooDeep { position: 255 })
+        );
+
+        let deepest = parse_line(&deepest);
+        assert_eq!(concatenate(&deepest, &parse_line("[1,1]")), None);
+        assert_eq!(deepest.checked_add(&parse_line("[1,1]")), None);
+        assert!(concatenate(&parse_line(&nested(254)), &parse_line("[1,1]")).is_some());
+    }
+
+    #[test]
+    fn test_multi_digit() {
+        let cases = [
+            "[[10,3],[1,12]]",
+            "[[[[0,7],4],[15,[0,13]]],[1,1]]",
+            "[4294967295,0]",
+        ];
+        for case in cases {
+            assert_eq!(parse_line(case).to_string(), case);
+        }
+
+        let mut number = parse_line("[[[[0,7],4],[15,[0,13]]],[1,1]]");
+        reduce(&mut number);
+        assert_eq!(number, parse_line("[[[[0,7],4],[[7,8],[6,0]]],[8,1]]"));
+
+        assert_eq!(
+            "[4294967296,0]".parse::<SnailfishNumber>(),
+            Err(ParseError::NumberTooLarge { position: 1 })
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        // The [1,1] ends up nested inside five pairs, and its left half lands on the huge number.
+        let lhs = parse_line("[4294967295,[[[1,1],1],1]]");
+        assert_eq!(lhs.checked_add(&parse_line("[1,1]")), None);
+
+        let deep = format!("{}4294967295{}", "[".repeat(40), ",0]".repeat(40));
+        assert_eq!(parse_line(&deep).checked_magnitude(), None);
+        assert_eq!(
+            parse_line("[4294967295,4294967295]").magnitude(),
+            5 * 4294967295
+        );
+    }
+
+    #[test]
+    fn test_structural_eq() {
+        // The same tree, built by concatenation and by parsing.
+        let built = concatenate(&parse_line("[1,2]"), &parse_line("[[3,4],5]")).unwrap();
+        assert_eq!(built, parse_line("[[1,2],[[3,4],5]]"));
+        assert_ne!(built, parse_line("[[1,2],[3,[4,5]]]"));
+        assert_ne!(parse_line("[[1,2],3]"), parse_line("[1,[2,3]]"));
+    }
+
     #[test]
     fn test_concatenate() {
-        let actual = concatenate(&parse_line("[1,2]"), &parse_line("[[3,4],5]"));
+        let actual = concatenate(&parse_line("[1,2]"), &parse_line("[[3,4],5]")).unwrap();
         let expected = parse_line("[[1,2],[[3,4],5]]");
         assert_eq!(format!("{}", actual), format!("{}", expected));
     }
 
+    #[test]
+    fn test_reduce() {
+        let cases = [
+            ("[[[[[9,8],1],2],3],4]", "[[[[0,9],2],3],4]"),
+            ("[7,[6,[5,[4,[3,2]]]]]", "[7,[6,[5,[7,0]]]]"),
+            ("[[6,[5,[4,[3,2]]]],1]", "[[6,[5,[7,0]]],3]"),
+            (
+                "[[3,[2,[1,[7,3]]]],[6,[5,[4,[3,2]]]]]",
+                "[[3,[2,[8,0]]],[9,[5,[7,0]]]]",
+            ),
+            (
+                "[[[[[4,3],4],4],[7,[[8,4],9]]],[1,1]]",
+                "[[[[0,7],4],[[7,8],[6,0]]],[8,1]]",
+            ),
+        ];
+        for (input, expected) in cases {
+            let mut number = parse_line(input);
+            reduce(&mut number);
+            assert_eq!(number, parse_line(expected));
+        }
+    }
+
+    #[test]
+    fn test_reduce_traced() {
+        let sum = concatenate(
+            &parse_line("[[[[4,3],4],4],[7,[[8,4],9]]]"),
+            &parse_line("[1,1]"),
+        )
+        .unwrap();
+        let trace: Vec<_> = reduce_traced(&sum)
+            .iter()
+            .map(|(action, number)| format!("{}: {}", action, number))
+            .collect();
+        assert_eq!(
+            trace,
+            [
+                "Explode at path LLLL: [[[[0,7],4],[7,[[8,4],9]]],[1,1]]",
+                "Explode at path LRRL: [[[[0,7],4],[15,[0,13]]],[1,1]]",
+                "Split 15: [[[[0,7],4],[[7,8],[0,13]]],[1,1]]",
+                "Split 13: [[[[0,7],4],[[7,8],[0,[6,7]]]],[1,1]]",
+                "Explode at path LRRR: [[[[0,7],4],[[7,8],[6,0]]],[8,1]]",
+            ]
+        );
+
+        // The step-by-step reduction must agree with the fast one.
+        let data = parse(TEST_INPUT_5);
+        for p1 in data.iter() {
+            for p2 in data.iter() {
+                let trace = reduce_traced(&concatenate(p1, p2).unwrap());
+                assert_eq!(trace.last().unwrap().1, p1 + p2);
+            }
+        }
+    }
+
+    // Numbers nested six or more deep cascade explosions within a single pass.
+    #[test]
+    fn test_deep_nesting() {
+        let lhs = parse_line("[[[[[1,[2,3]],4],0],0],0]");
+        let rhs = parse_line("[0,0]");
+        assert_eq!(&lhs + &rhs, parse_line("[[[[0,7],0],0],[0,0]]"));
+
+        let deep = [
+            "[[[[[1,[2,3]],4],0],0],0]",
+            "[[[[[[[1,2],3],4],5],6],7],8]",
+            "[1,[2,[3,[4,[5,[6,[7,8]]]]]]]",
+            "[[[[[[9,9],[9,9]],[[9,9],[9,9]]],0],0],0]",
+            "[[[[[1,[[2,3],4]],[[5,6],7]],8],9],[[[[1,2],[3,[4,5]]],6],7]]",
+            "[0,0]",
+            "[[9,[9,[9,[9,9]]]],[[[[9,9],9],9],9]]",
+        ]
+        .map(parse_line);
+        for p1 in deep.iter() {
+            for p2 in deep.iter() {
+                let trace = reduce_traced(&concatenate(p1, p2).unwrap());
+                let expected = match trace.last() {
+                    Some((_, number)) => number.clone(),
+                    None => concatenate(p1, p2).unwrap(),
+                };
+                assert_eq!(p1 + p2, expected, "{} + {}", p1, p2);
+            }
+        }
+    }
+
     #[test]
     fn part1_known() {
         let lhs = parse_line("[[[[4,3],4],4],[7,[[8,4],9]]]");
         let rhs = parse_line("[1,1]");
         let answer = parse_line("[[[[0,7],4],[[7,8],[6,0]]],[8,1]]");
-        assert_eq!(add(&lhs, &rhs), answer);
+        assert_eq!(lhs + rhs, answer);
 
         assert_eq!(
-            final_sum(&parse(&TEST_INPUT_1)),
+            parse(TEST_INPUT_1).iter().sum::<SnailfishNumber>(),
             parse_line("[[[[1,1],[2,2]],[3,3]],[4,4]]")
         );
         assert_eq!(
-            final_sum(&parse(&TEST_INPUT_2)),
+            parse(TEST_INPUT_2).iter().sum::<SnailfishNumber>(),
             parse_line("[[[[3,0],[5,3]],[4,4]],[5,5]]")
         );
         assert_eq!(
-            final_sum(&parse(&TEST_INPUT_3)),
+            parse(TEST_INPUT_3).iter().sum::<SnailfishNumber>(),
             parse_line("[[[[5,0],[7,4]],[5,5]],[6,6]]")
         );
         assert_eq!(
-            final_sum(&parse(&TEST_INPUT_4)),
+            parse(TEST_INPUT_4).iter().sum::<SnailfishNumber>(),
             parse_line("[[[[8,7],[7,7]],[[8,6],[7,7]]],[[[0,7],[6,6]],[8,7]]]")
         );
 
-        assert_eq!(magnitude(&parse_line("[9,1]")), 29);
-        assert_eq!(magnitude(&parse_line("[[9,1],[1,9]]")), 129);
+        assert_eq!(parse_line("[9,1]").magnitude(), 29);
+        assert_eq!(parse_line("[[9,1],[1,9]]").magnitude(), 129);
 
-        assert_eq!(magnitude(&parse_line("[[1,2],[[3,4],5]]")), 143);
+        assert_eq!(parse_line("[[1,2],[[3,4],5]]").magnitude(), 143);
         assert_eq!(
-            magnitude(&parse_line("[[[[0,7],4],[[7,8],[6,0]]],[8,1]]")),
+            parse_line("[[[[0,7],4],[[7,8],[6,0]]],[8,1]]").magnitude(),
             1384
         );
-        assert_eq!(magnitude(&parse_line("[[[[1,1],[2,2]],[3,3]],[4,4]]")), 445);
-        assert_eq!(magnitude(&parse_line("[[[[3,0],[5,3]],[4,4]],[5,5]]")), 791);
+        assert_eq!(parse_line("[[[[1,1],[2,2]],[3,3]],[4,4]]").magnitude(), 445);
+        assert_eq!(parse_line("[[[[3,0],[5,3]],[4,4]],[5,5]]").magnitude(), 791);
         assert_eq!(
-            magnitude(&parse_line("[[[[5,0],[7,4]],[5,5]],[6,6]]")),
+            parse_line("[[[[5,0],[7,4]],[5,5]],[6,6]]").magnitude(),
             1137
         );
         assert_eq!(
-            magnitude(&parse_line(
-                "[[[[8,7],[7,7]],[[8,6],[7,7]]],[[[0,7],[6,6]],[8,7]]]"
-            )),
+            parse_line("[[[[8,7],[7,7]],[[8,6],[7,7]]],[[[0,7],[6,6]],[8,7]]]").magnitude(),
             3488
         );
 
@@ -470,18 +725,22 @@ mod tests {
 
     #[test]
     fn part2_known() {
-        let sum = add(
-            &parse_line("[[2,[[7,7],7]],[[5,8],[[9,3],[0,2]]]]"),
-            &parse_line("[[[0,[5,8]],[[1,7],[9,6]]],[[4,[1,2]],[[1,4],2]]]"),
-        );
+        let sum = parse_line("[[2,[[7,7],7]],[[5,8],[[9,3],[0,2]]]]")
+            + parse_line("[[[0,[5,8]],[[1,7],[9,6]]],[[4,[1,2]],[[1,4],2]]]");
         assert_eq!(
             sum,
             parse_line("[[[[7,8],[6,6]],[[6,0],[7,7]]],[[[7,8],[8,8]],[[7,9],[0,6]]]]")
         );
-        assert_eq!(magnitude(&sum), 3993);
+        assert_eq!(sum.magnitude(), 3993);
 
-        let data = parse(&TEST_INPUT_5);
+        let data = parse(TEST_INPUT_5);
         assert_eq!(part_2(&data), 3993);
+        assert_eq!(best_pair(&data), Some((3993, 8, 0)));
+
+        // Both copies of [1,1] give the same best magnitude; the first pair wins.
+        let data = parse("[1,1]\n[2,2]\n[1,1]");
+        assert_eq!(best_pair(&data), Some((40, 1, 0)));
+        assert_eq!(best_pair(&data[..1]), None);
     }
 
     #[test]
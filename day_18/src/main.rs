@@ -1,7 +1,46 @@
-use day_18::day_18::{input, part_1, part_2};
+use day_18::day_18::{concatenate, input, part_1, part_2, reduce_traced, SnailfishNumber};
+
+// The worked example of a single addition from the puzzle text.
+static EXAMPLE: [&str; 2] = ["[[[[4,3],4],4],[7,[[8,4],9]]]", "[1,1]"];
+
+/// Add up the given numbers, printing every step of every reduction.
+fn trace(numbers: &[String]) {
+    let mut numbers = numbers.iter().map(|s| match s.parse::<SnailfishNumber>() {
+        Ok(number) => number,
+        Err(e) => panic!("Could not parse {}: {:?}", s, e),
+    });
+    let mut total = numbers.next().expect("Expected at least one number");
+    for number in numbers {
+        println!("  {}", total);
+        println!("+ {}", number);
+        let sum = concatenate(&total, &number).expect("Nested more than 255 pairs deep");
+        println!("after addition: {}", sum);
+        let mut reduced = sum;
+        for (action, after) in reduce_traced(&reduced) {
+            println!("{}: {}", action, after);
+            reduced = after;
+        }
+        println!("= {}", reduced);
+        println!();
+        total = reduced;
+    }
+    println!("magnitude => {}", total.magnitude());
+}
 
 fn main() {
-    let input = input();
-    println!("part 1 => {}", part_1(&input));
-    println!("part 2 => {}", part_2(&input));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.split_first() {
+        Some((command, numbers)) if command == "trace" => {
+            if numbers.is_empty() {
+                trace(&EXAMPLE.map(String::from));
+            } else {
+                trace(numbers);
+            }
+        }
+        _ => {
+            let input = input();
+            println!("part 1 => {}", part_1(&input));
+            println!("part 2 => {}", part_2(&input));
+        }
+    }
 }
@@ -1,14 +1,16 @@
 pub mod day_2 {
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum Direction {
         Forward,
         Down,
         Up,
     }
 
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub struct Movement {
-        direction: Direction,
-        distance: u32,
+        pub direction: Direction,
+        pub distance: u32,
     }
 
     fn chomp<I>(input: &mut I, s: &str)
@@ -61,77 +63,119 @@ pub mod day_2 {
         }
     }
 
+    /// Parse one movement per line.
+    pub fn parse(s: &str) -> Vec<Movement> {
+        s.trim().split('\n').map(parse_movement).collect()
+    }
+
     pub fn input() -> Vec<Movement> {
-        let input = include_str!("../input.txt");
-        input
-            .trim()
-            .split('\n')
-            .map(parse_movement)
-            .collect::<Vec<Movement>>()
+        parse(include_str!("../input.txt"))
+    }
+
+    /// A way of interpreting the movements.
+    pub trait Submarine: Clone {
+        fn step(&mut self, movement: &Movement);
+
+        /// The horizontal position, and the depth (which is negative above the surface).
+        fn position(&self) -> (i64, i64);
+
+        /// Make all the movements in turn.
+        fn run<'a, I>(mut self, movements: I) -> Self
+        where
+            I: IntoIterator<Item = &'a Movement>,
+        {
+            for movement in movements {
+                self.step(movement);
+            }
+            self
+        }
+
+        /// The submarine after each of the movements in turn.
+        fn trace<'a, I>(self, movements: I) -> Trace<Self, I::IntoIter>
+        where
+            I: IntoIterator<Item = &'a Movement>,
+        {
+            Trace {
+                submarine: self,
+                movements: movements.into_iter(),
+            }
+        }
     }
 
+    pub struct Trace<S, I> {
+        submarine: S,
+        movements: I,
+    }
+
+    impl<'a, S, I> Iterator for Trace<S, I>
+    where
+        S: Submarine,
+        I: Iterator<Item = &'a Movement>,
+    {
+        type Item = S;
+
+        fn next(&mut self) -> Option<S> {
+            self.submarine.step(self.movements.next()?);
+            Some(self.submarine.clone())
+        }
+    }
+
+    /// Up and down move the submarine directly.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
     pub struct Position {
-        x: u32,
-        y: u32,
-    }
-
-    const fn step(position: Position, movement: &Movement) -> Position {
-        match movement.direction {
-            Direction::Forward => Position {
-                x: position.x + movement.distance,
-                y: position.y,
-            },
-            Direction::Up => Position {
-                x: position.x,
-                y: position.y - movement.distance,
-            },
-            Direction::Down => Position {
-                x: position.x,
-                y: position.y + movement.distance,
-            },
+        pub x: i64,
+        pub y: i64,
+    }
+
+    impl Submarine for Position {
+        fn step(&mut self, movement: &Movement) {
+            let distance = movement.distance as i64;
+            match movement.direction {
+                Direction::Forward => self.x += distance,
+                Direction::Up => self.y -= distance,
+                Direction::Down => self.y += distance,
+            }
+        }
+
+        fn position(&self) -> (i64, i64) {
+            (self.x, self.y)
         }
     }
 
-    pub fn part_1(movements: &[Movement]) -> u32 {
-        let start_pos = Position { x: 0, y: 0 };
-        let final_pos = movements.iter().fold(start_pos, step);
-        final_pos.x * final_pos.y
+    pub fn part_1(movements: &[Movement]) -> i64 {
+        let (x, y) = Position::default().run(movements).position();
+        x * y
     }
 
+    /// Up and down change the aim, and moving forward also moves along the aim.
+    #[derive(Debug, Clone, Default, PartialEq, Eq)]
     pub struct Position2 {
-        x: u32,
-        depth: u32,
-        aim: u32,
-    }
-
-    const fn step_2(position: Position2, movement: &Movement) -> Position2 {
-        match movement.direction {
-            Direction::Forward => Position2 {
-                aim: position.aim,
-                x: position.x + movement.distance,
-                depth: position.depth + movement.distance * position.aim,
-            },
-            Direction::Up => Position2 {
-                x: position.x,
-                depth: position.depth,
-                aim: position.aim - movement.distance,
-            },
-            Direction::Down => Position2 {
-                x: position.x,
-                depth: position.depth,
-                aim: position.aim + movement.distance,
-            },
+        pub x: i64,
+        pub depth: i64,
+        pub aim: i64,
+    }
+
+    impl Submarine for Position2 {
+        fn step(&mut self, movement: &Movement) {
+            let distance = movement.distance as i64;
+            match movement.direction {
+                Direction::Forward => {
+                    self.x += distance;
+                    self.depth += distance * self.aim;
+                }
+                Direction::Up => self.aim -= distance,
+                Direction::Down => self.aim += distance,
+            }
+        }
+
+        fn position(&self) -> (i64, i64) {
+            (self.x, self.depth)
         }
     }
 
-    pub fn part_2(movements: &[Movement]) -> u32 {
-        let start_pos = Position2 {
-            x: 0,
-            depth: 0,
-            aim: 0,
-        };
-        let final_pos = movements.iter().fold(start_pos, step_2);
-        final_pos.x * final_pos.depth
+    pub fn part_2(movements: &[Movement]) -> i64 {
+        let (x, depth) = Position2::default().run(movements).position();
+        x * depth
     }
 }
 
@@ -165,10 +209,74 @@ mod tests {
             "forward 2",
         ]
         .map(parse_movement);
-        println!("{}", part_2(&input));
         assert_eq!(part_2(&input), 900);
     }
 
+    #[test]
+    fn trace_known() {
+        let input = parse("forward 5\ndown 5\nforward 8\nup 3\ndown 8\nforward 2");
+        let depths: Vec<_> = Position2::default()
+            .trace(&input)
+            .map(|p| (p.position(), p.aim))
+            .collect();
+        assert_eq!(
+            depths,
+            vec![
+                ((5, 0), 0),
+                ((5, 0), 5),
+                ((13, 40), 5),
+                ((13, 40), 2),
+                ((13, 40), 10),
+                ((15, 60), 10)
+            ]
+        );
+    }
+
+    #[test]
+    fn above_the_surface() {
+        let input = parse("up 3\nforward 2\ndown 1");
+        assert_eq!(Position::default().run(&input).position(), (2, -2));
+        assert_eq!(part_1(&input), -4);
+        assert_eq!(Position2::default().run(&input).position(), (2, -6));
+        assert_eq!(part_2(&input), -12);
+    }
+
+    // A third interpretation needs only its own steps.
+    #[derive(Clone)]
+    struct Reversed(Position);
+
+    impl Submarine for Reversed {
+        fn step(&mut self, movement: &Movement) {
+            let direction = match movement.direction {
+                Direction::Up => Direction::Down,
+                Direction::Down => Direction::Up,
+                Direction::Forward => Direction::Forward,
+            };
+            self.0.step(&Movement {
+                direction,
+                distance: movement.distance,
+            });
+        }
+
+        fn position(&self) -> (i64, i64) {
+            self.0.position()
+        }
+    }
+
+    #[test]
+    fn other_submarines() {
+        let input = input();
+        let (x, y) = Position::default().run(&input).position();
+        assert_eq!(
+            Reversed(Position::default()).run(&input).position(),
+            (x, -y)
+        );
+        assert_eq!(
+            Reversed(Position::default()).trace(&input).count(),
+            input.len()
+        );
+    }
+
     #[test]
     fn test_day_2() {
         let input = input();
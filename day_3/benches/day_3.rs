@@ -2,7 +2,7 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion};
 use day_3::day_3::{input, part_1, part_2};
 
 fn criterion_benchmark(c: &mut Criterion) {
-    let input = input::<12>();
+    let input = input();
     c.bench_function("day 3 part 1", |b| {
         b.iter(|| {
             black_box(part_1(&input));
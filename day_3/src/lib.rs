@@ -2,170 +2,163 @@ pub mod day_3 {
 
     use std::cmp::Ordering;
 
-    pub(crate) fn parse<const N: usize>(s: &str) -> [bool; N] {
-        let mut answer = [false; N];
-        for (i, c) in s.chars().enumerate() {
-            answer[i] = c != '0';
+    /// The diagnostic report: rows of `width` bits, most significant bit first.
+    #[derive(Debug, Clone)]
+    pub struct Report {
+        width: usize,
+        rows: Vec<u64>,
+    }
+
+    impl Report {
+        pub fn width(&self) -> usize {
+            self.width
+        }
+
+        // The mask for the column `col` bits in from the left.
+        fn bit(&self, col: usize) -> u64 {
+            1 << (self.width - 1 - col)
+        }
+
+        // How many rows have a 1 in the given column.
+        fn ones(&self, col: usize) -> usize {
+            let bit = self.bit(col);
+            self.rows.iter().filter(|&&row| row & bit != 0).count()
+        }
+
+        fn mask(&self) -> u64 {
+            u64::MAX >> (64 - self.width)
         }
-        answer
     }
 
-    pub fn input<const N: usize>() -> Vec<[bool; N]> {
-        let input = include_str!("../input.txt");
-        input
-            .trim()
-            .split('\n')
-            .map(parse)
-            .collect::<Vec<[bool; N]>>()
+    fn parse_row(s: &str) -> u64 {
+        s.chars().fold(0, |row, c| match c {
+            '0' => row << 1,
+            '1' => (row << 1) | 1,
+            c => panic!("Expected a bit, got '{}'", c),
+        })
     }
 
-    // lol interview prep
-    fn boyer_moore_majority_element<I, U>(input: I) -> U
-    where
-        U: Eq,
-        U: Copy,
-        I: Iterator<Item = U>,
-    {
-        let mut counter = 0;
-        let mut bag = None;
-        for elt in input {
-            if counter == 0 {
-                bag = Some(elt);
-                counter = 1;
-            } else if elt == bag.unwrap() {
-                counter += 1;
-            } else {
-                counter -= 1;
-            }
+    /// Parse rows of bits, all the same width (between 1 and 64).
+    pub(crate) fn parse(s: &str) -> Report {
+        let lines: Vec<&str> = s.trim().split('\n').collect();
+        let width = lines[0].len();
+        if width == 0 || width > 64 {
+            panic!("Expected between 1 and 64 bits per row, got {}", width);
         }
-        bag.unwrap()
+        let rows = lines
+            .iter()
+            .map(|line| {
+                if line.len() != width {
+                    panic!("Expected {} bits, got '{}'", width, line);
+                }
+                parse_row(line)
+            })
+            .collect();
+        Report { width, rows }
     }
 
-    fn most_common<const N: usize>(bits: &[[bool; N]], pos: usize) -> bool {
-        boyer_moore_majority_element(bits.iter().map(|arr| arr[pos]))
+    pub fn input() -> Report {
+        parse(include_str!("../input.txt"))
     }
 
-    pub fn part_1<const N: usize>(bits: &[[bool; N]]) -> u32 {
-        let mut gamma = 0;
-        let mut eps = 0;
-        for i in 0..N {
-            let most = most_common(bits, i);
-            gamma = gamma * 2 + u32::from(most);
-            eps = eps * 2 + u32::from(!most);
-        }
-        gamma * eps
+    /// Which bit gamma gets in a column with as many ones as zeros.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Tie {
+        One,
+        Zero,
     }
 
-    fn majority_element_weak<I>(input: I, default: bool) -> Option<bool>
-    where
-        I: Iterator<Item = bool>,
-    {
-        let (falses, trues) = input.fold((0, 0), |(falses, trues), elt| {
-            if elt {
-                (falses, trues + 1)
-            } else {
-                (falses + 1, trues)
-            }
-        });
-        if trues + falses == 1 {
-            return None;
-        }
+    /// The columns, counting from the left, with as many ones as zeros.
+    pub fn tied_columns(report: &Report) -> Vec<usize> {
+        (0..report.width)
+            .filter(|&col| 2 * report.ones(col) == report.rows.len())
+            .collect()
+    }
 
-        match trues.cmp(&falses) {
-            Ordering::Greater => Some(true),
-            Ordering::Less => Some(false),
-            Ordering::Equal => Some(default),
+    /// The gamma rate, made of the most common bit in each column, and the epsilon rate, made of
+    /// the least common. Epsilon is always the complement of gamma, even in tied columns.
+    pub fn gamma_epsilon(report: &Report, tie: Tie) -> (u64, u64) {
+        let mut gamma = 0;
+        for col in 0..report.width {
+            let bit = match (2 * report.ones(col)).cmp(&report.rows.len()) {
+                Ordering::Greater => true,
+                Ordering::Less => false,
+                Ordering::Equal => tie == Tie::One,
+            };
+            if bit {
+                gamma |= report.bit(col);
+            }
         }
+        (gamma, !gamma & report.mask())
     }
 
-    fn most_common_skipping<F, const N: usize>(
-        bits: &[[bool; N]],
-        pos: usize,
-        default: bool,
-        should_ignore: F,
-    ) -> Result<&[bool; N], bool>
-    where
-        F: Fn(&[bool; N]) -> bool,
-    {
-        let mut elt = None;
-
-        let majority = majority_element_weak(
-            bits.iter().filter(|&a| !should_ignore(a)).map(|arr| {
-                elt = Some(arr);
-                arr[pos]
-            }),
-            default,
-        );
-        match majority {
-            None => Ok(elt.unwrap()),
-            Some(b) => Err(b),
+    /// Panics if any column is tied, since the puzzle doesn't say what the most common bit is
+    /// then. The rates have up to 64 bits each, so their product may need 128.
+    pub fn part_1(report: &Report) -> u128 {
+        let tied = tied_columns(report);
+        if !tied.is_empty() {
+            panic!("Columns {:?} have no most common bit", tied);
         }
+        let (gamma, epsilon) = gamma_epsilon(report, Tie::One);
+        u128::from(gamma) * u128::from(epsilon)
     }
 
-    fn agree<T, const N: usize>(b1: &[T; N], b2: &[T; N], count: usize) -> bool
+    // Narrow down to a single row by repeatedly keeping the rows which have the chosen bit in
+    // the next column. `keep_ones(ones, zeros)` picks the bit, given how many of each there are,
+    // unless only one bit appears in the column. If a row is repeated, it may be all that's left
+    // before the columns run out, and then it's the rating.
+    // Sorting the rows first means each choice just splits the remaining range in two.
+    fn rating<F>(report: &Report, sorted: &[u64], keep_ones: F) -> u64
     where
-        T: Eq,
+        F: Fn(usize, usize) -> bool,
     {
-        for i in 0..count {
-            if b1[i] != b2[i] {
-                return false;
+        let mut remaining = sorted;
+        for col in 0..report.width {
+            // Sorted, so the remaining rows are all the same if the first and last are.
+            if remaining.first() == remaining.last() {
+                break;
             }
+            let bit = report.bit(col);
+            // The remaining rows agree on the columns before this one, so they're sorted by
+            // this column next.
+            let split = remaining.partition_point(|&row| row & bit == 0);
+            let (zeros, ones) = remaining.split_at(split);
+            remaining =
+                if zeros.is_empty() || (!ones.is_empty() && keep_ones(ones.len(), zeros.len())) {
+                    ones
+                } else {
+                    zeros
+                };
         }
-        true
+        remaining[0]
     }
 
-    fn to_u16<const N: usize>(bits: [bool; N]) -> u16 {
-        let mut ans = 0;
-        for b in bits.iter() {
-            ans = ans * 2 + u16::from(*b);
-        }
-
-        ans
+    fn sorted(report: &Report) -> Vec<u64> {
+        let mut sorted = report.rows.clone();
+        sorted.sort_unstable();
+        sorted
     }
 
-    pub fn oxygen<const N: usize>(bits: &[[bool; N]]) -> u16 {
-        let mut most_common_arr = [false; N];
-        for i in 0..N {
-            let most = most_common_skipping(bits, i, true, |arr| !agree(&most_common_arr, arr, i));
-            match most {
-                Ok(answer) => {
-                    return to_u16(*answer);
-                }
-                Err(b) => {
-                    most_common_arr[i] = b;
-                }
-            }
-        }
-        to_u16(most_common_arr)
+    pub fn oxygen(report: &Report) -> u64 {
+        rating(report, &sorted(report), |ones, zeros| ones >= zeros)
     }
 
-    pub fn co2<const N: usize>(bits: &[[bool; N]]) -> u16 {
-        let mut least_common_arr = [false; N];
-        for i in 0..N {
-            let most = most_common_skipping(bits, i, true, |arr| !agree(&least_common_arr, arr, i));
-            match most {
-                Ok(answer) => {
-                    return to_u16(*answer);
-                }
-                Err(b) => {
-                    least_common_arr[i] = !b;
-                }
-            }
-        }
-        to_u16(least_common_arr)
+    pub fn co2(report: &Report) -> u64 {
+        rating(report, &sorted(report), |ones, zeros| ones < zeros)
     }
 
-    pub fn part_2<const N: usize>(bits: &[[bool; N]]) -> u32 {
-        let oxygen = oxygen(bits);
-        let co = co2(bits);
-
-        (oxygen as u32) * (co as u32)
+    pub fn part_2(report: &Report) -> u128 {
+        let sorted = sorted(report);
+        let oxygen = rating(report, &sorted, |ones, zeros| ones >= zeros);
+        let co2 = rating(report, &sorted, |ones, zeros| ones < zeros);
+        u128::from(oxygen) * u128::from(co2)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::day_3::*;
+    use proptest::prelude::*;
 
     #[test]
     fn part1_known() {
@@ -173,7 +166,8 @@ mod tests {
             "00100", "11110", "10110", "10111", "10101", "01111", "00111", "11100", "10000",
             "11001", "00010", "01010",
         ]
-        .map(parse::<5>);
+        .join("\n");
+        let input = parse(&input);
 
         assert_eq!(part_1(&input), 198);
     }
@@ -184,13 +178,138 @@ mod tests {
             "00100", "11110", "10110", "10111", "10101", "01111", "00111", "11100", "10000",
             "11001", "00010", "01010",
         ]
-        .map(parse::<5>);
+        .join("\n");
+        let input = parse(&input);
         assert_eq!(part_2(&input), 230);
     }
 
+    #[test]
+    fn ratings_known() {
+        let input = parse(
+            "00100\n11110\n10110\n10111\n10101\n01111\n00111\n11100\n10000\n11001\n00010\n01010",
+        );
+        assert_eq!(input.width(), 5);
+        assert_eq!(oxygen(&input), 23);
+        assert_eq!(co2(&input), 10);
+
+        // Wider than 16 bits, and with columns where every row agrees.
+        let wide = "1".repeat(40);
+        let input = parse(&format!("{}0\n{}1\n0{}", wide, wide, wide));
+        assert_eq!(input.width(), 41);
+        let ones = (1u64 << 40) - 1;
+        assert_eq!(oxygen(&input), (ones << 1) | 1);
+        assert_eq!(co2(&input), ones);
+        // Every column is mostly ones, so epsilon is zero.
+        assert_eq!(part_1(&input), 0);
+
+        // Repeated rows can be all that's left.
+        let input = parse("101\n101\n011");
+        assert_eq!(oxygen(&input), 0b101);
+        assert_eq!(co2(&input), 0b011);
+        let input = parse("110\n110");
+        assert_eq!(oxygen(&input), 0b110);
+        assert_eq!(co2(&input), 0b110);
+    }
+
+    #[test]
+    fn products_known_wide() {
+        // The products of 64-bit rates don't fit in 64 bits.
+        let (high, low) = ("10".repeat(32), "01".repeat(32));
+        let input = parse(&format!("{}\n{}\n{}", high, high, low));
+        let (high, low) = (0xAAAA_AAAA_AAAA_AAAAu64, 0x5555_5555_5555_5555u64);
+        assert_eq!(part_1(&input), u128::from(high) * u128::from(low));
+        assert_eq!(part_2(&input), u128::from(high) * u128::from(low));
+    }
+
+    #[test]
+    fn ties_known() {
+        let input = parse("0011\n0101\n1001");
+        assert_eq!(tied_columns(&input), Vec::<usize>::new());
+        let input = parse("0011\n0101\n1001\n1110");
+        assert_eq!(tied_columns(&input), vec![0, 1, 2]);
+        assert_eq!(gamma_epsilon(&input, Tie::One), (0b1111, 0b0000));
+        assert_eq!(gamma_epsilon(&input, Tie::Zero), (0b0001, 0b1110));
+    }
+
+    #[test]
+    #[should_panic(expected = "Columns [0, 1, 2] have no most common bit")]
+    fn part1_ties() {
+        part_1(&parse("0011\n0101\n1001\n1110"));
+    }
+
+    // The puzzle's rules followed literally, on rows of bools.
+    fn gamma_epsilon_brute(rows: &[Vec<bool>], tie: Tie) -> (u64, u64) {
+        let (mut gamma, mut epsilon) = (0, 0);
+        for col in 0..rows[0].len() {
+            let ones = rows.iter().filter(|row| row[col]).count();
+            let zeros = rows.len() - ones;
+            let most = ones > zeros || (ones == zeros && tie == Tie::One);
+            gamma = gamma * 2 + u64::from(most);
+            epsilon = epsilon * 2 + u64::from(!most);
+        }
+        (gamma, epsilon)
+    }
+
+    fn rating_brute(rows: &[Vec<bool>], keep_most_common: bool) -> u64 {
+        let mut remaining = rows.to_vec();
+        let mut col = 0;
+        // Repeated rows may still be left when the columns run out.
+        while remaining.len() > 1 && col < rows[0].len() {
+            let ones = remaining.iter().filter(|row| row[col]).count();
+            let zeros = remaining.len() - ones;
+            let keep = if ones == 0 || zeros == 0 {
+                ones > 0
+            } else if keep_most_common {
+                ones >= zeros
+            } else {
+                ones < zeros
+            };
+            remaining.retain(|row| row[col] == keep);
+            col += 1;
+        }
+        remaining[0]
+            .iter()
+            .fold(0, |acc, &b| acc * 2 + u64::from(b))
+    }
+
+    fn arb_rows() -> impl Strategy<Value = Vec<Vec<bool>>> {
+        (1..=12usize).prop_flat_map(|width| {
+            prop::collection::vec(prop::collection::vec(any::<bool>(), width), 1..40)
+        })
+    }
+
+    fn to_report(rows: &[Vec<bool>]) -> Report {
+        let lines: Vec<String> = rows
+            .iter()
+            .map(|row| row.iter().map(|&b| if b { '1' } else { '0' }).collect())
+            .collect();
+        parse(&lines.join("\n"))
+    }
+
+    proptest! {
+        #[test]
+        fn gamma_epsilon_agrees(rows in arb_rows()) {
+            let report = to_report(&rows);
+            for tie in [Tie::One, Tie::Zero] {
+                prop_assert_eq!(gamma_epsilon(&report, tie), gamma_epsilon_brute(&rows, tie));
+            }
+            let tied: Vec<usize> = (0..rows[0].len())
+                .filter(|&col| 2 * rows.iter().filter(|row| row[col]).count() == rows.len())
+                .collect();
+            prop_assert_eq!(tied_columns(&report), tied);
+        }
+
+        #[test]
+        fn ratings_agree(rows in arb_rows()) {
+            let report = to_report(&rows);
+            prop_assert_eq!(oxygen(&report), rating_brute(&rows, true));
+            prop_assert_eq!(co2(&report), rating_brute(&rows, false));
+        }
+    }
+
     #[test]
     fn test_day_3() {
-        let input = input::<12>();
+        let input = input();
         assert_eq!(part_1(&input), 3374136);
         assert_eq!(part_2(&input), 4432698);
     }
@@ -1,7 +1,7 @@
 use day_3::day_3;
 
 fn main() {
-    let input = day_3::input::<12>();
+    let input = day_3::input();
     println!("part 1 => {}", day_3::part_1(&input));
     println!("part 2 => {}", day_3::part_2(&input));
 }
@@ -1,5 +1,38 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use day_4::day_4::{input, part_1, part_2};
+use day_4::day_4::{input, part_1, part_2, play, Data, Indexed, Rules};
+
+// Many boards of distinct numbers in 0..100, and a draw of all of those numbers, shuffled
+// with a fixed linear congruential generator.
+fn generated(boards: usize) -> Data {
+    let mut state: u64 = 0x2021_0004;
+    let mut next = |bound: usize| {
+        state = state
+            .wrapping_mul(6364136223846793005)
+            .wrapping_add(1442695040888963407);
+        (state >> 33) as usize % bound
+    };
+    let mut shuffled = |n: usize| {
+        let mut numbers: Vec<usize> = (0..100).collect();
+        for i in 0..n {
+            let j = i + next(100 - i);
+            numbers.swap(i, j);
+        }
+        numbers.truncate(n);
+        numbers
+    };
+
+    let draws: Vec<String> = shuffled(100).iter().map(|i| i.to_string()).collect();
+    let mut s = draws.join(",");
+    for _ in 0..boards {
+        s.push('\n');
+        for row in shuffled(25).chunks(5) {
+            s.push('\n');
+            let row: Vec<String> = row.iter().map(|i| format!("{:2}", i)).collect();
+            s.push_str(&row.join(" "));
+        }
+    }
+    Data::parse(&s)
+}
 
 fn criterion_benchmark(c: &mut Criterion) {
     let input = input();
@@ -10,11 +43,30 @@ fn criterion_benchmark(c: &mut Criterion) {
     });
     c.bench_function("day 4 part 2", |b| {
         b.iter(|| {
-            let input = input.clone();
-            black_box(part_2(input));
+            black_box(part_2(&input));
+        })
+    });
+}
+
+fn generated_benchmark(c: &mut Criterion) {
+    let data = generated(5000);
+    c.bench_function("day 4 5000 boards, scanning", |b| {
+        b.iter(|| {
+            black_box(play(&data, Rules::PUZZLE));
+        })
+    });
+    c.bench_function("day 4 5000 boards, building the index", |b| {
+        b.iter(|| {
+            black_box(Indexed::new(&data));
+        })
+    });
+    let indexed = Indexed::new(&data);
+    c.bench_function("day 4 5000 boards, indexed", |b| {
+        b.iter(|| {
+            black_box(indexed.play(Rules::PUZZLE));
         })
     });
 }
 
-criterion_group!(benches, criterion_benchmark);
+criterion_group!(benches, criterion_benchmark, generated_benchmark);
 criterion_main!(benches);
@@ -1,36 +1,59 @@
 pub mod day_4 {
 
+    use std::collections::HashMap;
+    use std::fmt::Debug;
+    use std::hash::Hash;
+    use std::str::FromStr;
+
+    /// The numbers which can appear on boards and be drawn.
+    pub trait Number: Copy + Eq + Hash + Debug + FromStr + Into<u64> {}
+
+    impl<T> Number for T where T: Copy + Eq + Hash + Debug + FromStr + Into<u64> {}
+
     #[derive(Clone, Debug)]
-    pub struct Board {
-        arr: [[(u8, bool); 5]; 5],
+    pub struct Board<T, const N: usize> {
+        arr: [[(T, bool); N]; N],
     }
 
     #[derive(Clone, Debug)]
-    pub struct Data {
-        boards: Vec<Board>,
-        draws: Vec<u8>,
+    pub struct Data<T = u8, const N: usize = 5> {
+        boards: Vec<Board<T, N>>,
+        draws: Vec<T>,
     }
 
-    fn chomp_board<'a, I>(input: &mut I) -> Option<Board>
+    fn parse_number<T>(s: &str) -> T
+    where
+        T: Number,
+        <T as FromStr>::Err: Debug,
+    {
+        match s.parse::<T>() {
+            Ok(i) => i,
+            Err(e) => panic!("Could not parse {}: {:?}", s, e),
+        }
+    }
+
+    fn chomp_board<'a, I, T, const N: usize>(input: &mut I) -> Option<Board<T, N>>
     where
         I: Iterator<Item = &'a str>,
+        T: Number,
+        <T as FromStr>::Err: Debug,
     {
         match input.next() {
             None => None,
             Some("") => {
-                let mut answer = [[(0, false); 5]; 5];
-                for row in &mut answer {
-                    let numbers = input
+                let arr = std::array::from_fn(|_| {
+                    let row: Vec<T> = input
                         .next()
                         .unwrap()
                         .split_whitespace()
-                        .map(str::parse::<u8>)
-                        .map(|i| i.unwrap());
-                    for (j, entry) in numbers.enumerate() {
-                        row[j] = (entry, false);
+                        .map(parse_number)
+                        .collect();
+                    if row.len() != N {
+                        panic!("Expected a row of {} numbers, got {:?}", N, row);
                     }
-                }
-                Some(Board { arr: answer })
+                    std::array::from_fn(|j| (row[j], false))
+                });
+                Some(Board { arr })
             }
             Some(l) => {
                 panic!("Expected an empty line, got {}", l);
@@ -38,111 +61,233 @@ pub mod day_4 {
         }
     }
 
-    pub(crate) fn parse(s: &str) -> Data {
-        let mut input = s.split('\n');
-        if let Some(first_line) = input.next() {
-            let draws = first_line
-                .split(',')
-                .map(str::parse::<u8>)
-                .map(|i| i.unwrap())
-                .collect::<Vec<u8>>();
-            let mut boards = Vec::new();
-            while let Some(board) = chomp_board(&mut input) {
-                boards.push(board);
+    impl<T, const N: usize> Data<T, N>
+    where
+        T: Number,
+        <T as FromStr>::Err: Debug,
+    {
+        /// Parse a list of draws followed by any number of N-by-N boards.
+        pub fn parse(s: &str) -> Data<T, N> {
+            let mut input = s.split('\n');
+            if let Some(first_line) = input.next() {
+                let draws = first_line.split(',').map(parse_number).collect();
+                let mut boards = Vec::new();
+                while let Some(board) = chomp_board(&mut input) {
+                    boards.push(board);
+                }
+                Data { boards, draws }
+            } else {
+                panic!("Unexpectedly no first line");
             }
-            Data { boards, draws }
-        } else {
-            panic!("Unexpectedly no first line");
         }
     }
 
+    pub(crate) fn parse(s: &str) -> Data {
+        Data::parse(s)
+    }
+
     pub fn input() -> Data {
         parse(include_str!("../input.txt"))
     }
 
-    fn draw_one(number: u8, board: &mut Board) -> bool {
-        for row in 0..5 {
-            for col in 0..5 {
+    /// What counts as a win.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct Rules {
+        /// Whether a full diagonal wins, as well as a full row or column.
+        pub diagonals: bool,
+    }
+
+    impl Rules {
+        /// The rules in the puzzle: only rows and columns win.
+        pub const PUZZLE: Rules = Rules { diagonals: false };
+    }
+
+    // Whether every cell in the line is marked, where `cell(i)` is its i'th (row, col).
+    fn full<T, F, const N: usize>(board: &Board<T, N>, cell: F) -> bool
+    where
+        F: Fn(usize) -> (usize, usize),
+    {
+        (0..N).all(|i| {
+            let (row, col) = cell(i);
+            board.arr[row][col].1
+        })
+    }
+
+    fn draw_one<T, const N: usize>(number: T, board: &mut Board<T, N>, rules: Rules) -> bool
+    where
+        T: Number,
+    {
+        for row in 0..N {
+            for col in 0..N {
                 if board.arr[row][col].0 == number {
                     board.arr[row][col].1 = true;
-                    // Check row
-                    let mut row_full = true;
-                    for i in 0..5 {
-                        row_full = row_full && board.arr[row][i].1;
-                    }
-                    if row_full {
-                        return true;
-                    }
-                    // Check column
-                    for i in 0..5 {
-                        if !board.arr[i][col].1 {
-                            return false;
-                        }
-                    }
-                    return true;
+                    return full(board, |i| (row, i))
+                        || full(board, |i| (i, col))
+                        || (rules.diagonals && row == col && full(board, |i| (i, i)))
+                        || (rules.diagonals
+                            && row + col == N - 1
+                            && full(board, |i| (i, N - 1 - i)));
                 }
             }
         }
         false
     }
 
-    /// Draw the given number, update the input boards, and return the
-    /// winning one if one has just won.
-    fn draw(number: u8, boards: &mut [Board], seen_arr: &mut [bool]) -> Option<usize> {
-        let mut ans = None;
-        for (i, (board, seen)) in boards.iter_mut().zip(seen_arr).enumerate() {
-            if !*seen && draw_one(number, board) {
-                *seen = true;
-                ans = Some(i);
+    fn sum_unmarked<T, const N: usize>(board: &Board<T, N>) -> u64
+    where
+        T: Number,
+    {
+        board
+            .arr
+            .iter()
+            .flatten()
+            .filter(|(_, has_seen)| !has_seen)
+            .map(|&(elt, _)| elt.into())
+            .sum()
+    }
+
+    /// A board completing a line.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct Win<T> {
+        /// The index of the board in the input.
+        pub board: usize,
+        /// The number whose draw completed the line.
+        pub draw: T,
+        /// The sum of the unmarked numbers on the board, times the draw.
+        pub score: u64,
+    }
+
+    /// Draw every number, returning each board which ever wins in the order they win.
+    /// Boards winning on the same draw are in input order.
+    /// This checks every cell of every board on each draw; `Indexed` is faster.
+    pub fn play<T, const N: usize>(data: &Data<T, N>, rules: Rules) -> Vec<Win<T>>
+    where
+        T: Number,
+    {
+        let mut boards = data.boards.clone();
+        let mut won = vec![false; boards.len()];
+        let mut wins = Vec::new();
+        for &number in data.draws.iter() {
+            for (i, (board, won)) in boards.iter_mut().zip(won.iter_mut()).enumerate() {
+                if !*won && draw_one(number, board, rules) {
+                    *won = true;
+                    wins.push(Win {
+                        board: i,
+                        draw: number,
+                        score: sum_unmarked(board) * number.into(),
+                    });
+                }
             }
         }
-        ans
+        wins
     }
 
-    fn sum_unmarked(board: &Board) -> u32 {
-        let mut ans = 0;
-        for row in 0..5 {
-            for col in 0..5 {
-                let (elt, has_seen) = board.arr[row][col];
-                if !has_seen {
-                    ans += elt as u32;
-                }
+    // How many cells of each line of a board have been marked so far.
+    struct Tally<const N: usize> {
+        rows: [usize; N],
+        cols: [usize; N],
+        diagonal: usize,
+        anti_diagonal: usize,
+        unmarked: u64,
+        won: bool,
+    }
+
+    impl<const N: usize> Tally<N> {
+        // Mark the cell, returning whether that completed a line.
+        fn mark(&mut self, row: usize, col: usize, rules: Rules) -> bool {
+            self.rows[row] += 1;
+            self.cols[col] += 1;
+            if row == col {
+                self.diagonal += 1;
+            }
+            if row + col == N - 1 {
+                self.anti_diagonal += 1;
             }
+            self.rows[row] == N
+                || self.cols[col] == N
+                || (rules.diagonals && (self.diagonal == N || self.anti_diagonal == N))
         }
-        ans
     }
 
-    pub fn part_1(data: &Data) -> u32 {
-        let mut data = data.clone();
-        let mut seen = vec![false; data.boards.len()];
-        for number in data.draws.iter() {
-            match draw(*number, &mut data.boards, &mut seen) {
-                None => {}
-                Some(board) => {
-                    return sum_unmarked(&data.boards[board]) * (*number as u32);
+    // A board, and a row and column on it.
+    type Place = (usize, usize, usize);
+
+    /// A game prepared for `play`ing quickly: for each draw, where the number is on the boards,
+    /// so that a draw takes time proportional to the number of boards it appears on.
+    pub struct Indexed<T, const N: usize> {
+        // Each number in the order it's drawn, with each (board, row, col) it marks. A number
+        // only marks its first place on a board, and drawing it again marks nothing new.
+        schedule: Vec<(T, Vec<Place>)>,
+        unmarked: Vec<u64>,
+    }
+
+    impl<T, const N: usize> Indexed<T, N>
+    where
+        T: Number,
+    {
+        pub fn new(data: &Data<T, N>) -> Indexed<T, N> {
+            let mut index: HashMap<T, Vec<Place>> = HashMap::new();
+            for (i, board) in data.boards.iter().enumerate() {
+                for (row, cells) in board.arr.iter().enumerate() {
+                    for (col, &(number, _)) in cells.iter().enumerate() {
+                        let places = index.entry(number).or_default();
+                        if places.last().map(|&(board, _, _)| board) != Some(i) {
+                            places.push((i, row, col));
+                        }
+                    }
                 }
             }
+            let schedule = data
+                .draws
+                .iter()
+                .filter_map(|number| Some((*number, index.remove(number)?)))
+                .collect();
+            let unmarked = data.boards.iter().map(sum_unmarked).collect();
+            Indexed { schedule, unmarked }
         }
-        panic!("Expected a winning board");
-    }
-
-    pub fn part_2(mut data: Data) -> u32 {
-        let mut seen: Vec<bool> = vec![false; data.boards.len()];
-        let mut last_won = None;
-        for number in data.draws.iter() {
-            match draw(*number, &mut data.boards, &mut seen) {
-                None => {}
-                Some(board) => {
-                    seen[board] = true;
-                    last_won = Some((board, *number));
-                    if seen.iter().all(|&i| i) {
-                        return sum_unmarked(&data.boards[board]) * (*number as u32);
+
+        /// The same as `play` on the data this was made from.
+        pub fn play(&self, rules: Rules) -> Vec<Win<T>> {
+            let mut tallies: Vec<_> = self
+                .unmarked
+                .iter()
+                .map(|&unmarked| Tally::<N> {
+                    rows: [0; N],
+                    cols: [0; N],
+                    diagonal: 0,
+                    anti_diagonal: 0,
+                    unmarked,
+                    won: false,
+                })
+                .collect();
+
+            let mut wins = Vec::new();
+            for (number, places) in self.schedule.iter() {
+                for &(board, row, col) in places {
+                    let tally = &mut tallies[board];
+                    tally.unmarked -= (*number).into();
+                    if tally.mark(row, col, rules) && !tally.won {
+                        tally.won = true;
+                        wins.push(Win {
+                            board,
+                            draw: *number,
+                            score: tally.unmarked * (*number).into(),
+                        });
                     }
                 }
             }
+            wins
         }
-        let (last_won_index, number) = last_won.unwrap();
-        sum_unmarked(&data.boards[last_won_index]) * (number as u32)
+    }
+
+    pub fn part_1(data: &Data) -> u64 {
+        let wins = Indexed::new(data).play(Rules::PUZZLE);
+        wins.first().expect("Expected a winning board").score
+    }
+
+    pub fn part_2(data: &Data) -> u64 {
+        let wins = Indexed::new(data).play(Rules::PUZZLE);
+        wins.last().expect("Expected a winning board").score
     }
 }
 
@@ -182,13 +327,73 @@ mod tests {
     fn part2_known() {
         let data = parse(TEST_INPUT);
 
-        assert_eq!(part_2(data), 1924);
+        assert_eq!(part_2(&data), 1924);
+    }
+
+    #[test]
+    fn play_known() {
+        let data = parse(TEST_INPUT);
+        let win = |board, draw, score| Win { board, draw, score };
+        assert_eq!(
+            play(&data, Rules::PUZZLE),
+            vec![win(2, 24, 4512), win(0, 16, 2192), win(1, 13, 1924)]
+        );
+        // The last board's anti-diagonal 4, 9, 23, 11, 2 is complete before any line.
+        let marked = 7 + 4 + 9 + 5 + 11 + 17 + 23 + 2;
+        assert_eq!(
+            play(&data, Rules { diagonals: true })[0],
+            win(2, 2, 2 * (325 - marked))
+        );
+    }
+
+    #[test]
+    fn other_sizes() {
+        let data = Data::<u16, 3>::parse(
+            "1000,3,65535,2
+
+ 1000    1    2
+    3    4    5
+  500    6 1234
+
+65535    7    2
+    8    3   10
+ 1000   11   12",
+        );
+        let wins = play(&data, Rules::PUZZLE);
+        assert_eq!(wins.len(), 0);
+        let wins = play(&data, Rules { diagonals: true });
+        assert_eq!(
+            wins,
+            vec![Win {
+                board: 1,
+                draw: 2,
+                score: 2 * (7 + 8 + 10 + 11 + 12)
+            }]
+        );
+        let data = Data::<u32, 1>::parse("4000000000\n\n4000000000");
+        assert_eq!(play(&data, Rules::PUZZLE)[0].score, 0);
+    }
+
+    #[test]
+    fn indexed_agrees() {
+        let data = parse(TEST_INPUT);
+        for diagonals in [false, true] {
+            let rules = Rules { diagonals };
+            assert_eq!(Indexed::new(&data).play(rules), play(&data, rules));
+            assert_eq!(Indexed::new(&input()).play(rules), play(&input(), rules));
+        }
+        // Repeated draws, and repeated numbers on a board.
+        let data = Data::<u8, 2>::parse("1,1,2,3,3,4\n\n1 1\n2 3\n\n4 3\n2 2");
+        for diagonals in [false, true] {
+            let rules = Rules { diagonals };
+            assert_eq!(Indexed::new(&data).play(rules), play(&data, rules));
+        }
     }
 
     #[test]
     fn test_day_4() {
         let input = input();
         assert_eq!(part_1(&input), 64084);
-        assert_eq!(part_2(input), 12833);
+        assert_eq!(part_2(&input), 12833);
     }
 }
@@ -3,5 +3,5 @@ use day_4::day_4;
 fn main() {
     let input = day_4::input();
     println!("part 1 => {}", day_4::part_1(&input));
-    println!("part 2 => {}", day_4::part_2(input));
+    println!("part 2 => {}", day_4::part_2(&input));
 }
@@ -1,5 +1,5 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use day_5::day_5::{input, part_1, part_2};
+use day_5::day_5::{at_least_sparse, input, part_1, part_2, Angles};
 
 fn criterion_benchmark(c: &mut Criterion) {
     let input = input();
@@ -13,6 +13,11 @@ fn criterion_benchmark(c: &mut Criterion) {
             black_box(part_2(&input));
         })
     });
+    c.bench_function("day 5 part 2, sparse", |b| {
+        b.iter(|| {
+            black_box(at_least_sparse(&input, Angles::Octilinear, 2));
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -1,11 +1,13 @@
 pub mod day_5 {
 
-    use std::cmp::{max, min};
+    use std::cmp::max;
+    use std::collections::{BTreeMap, HashMap};
     use std::fmt;
+    use std::io;
 
     pub struct Coordinate {
-        x: u16,
-        y: u16,
+        x: u32,
+        y: u32,
     }
 
     pub struct Line {
@@ -14,7 +16,7 @@ pub mod day_5 {
     }
 
     fn parse_pair(s: &str) -> Coordinate {
-        let mut iterator = s.split(',').map(str::parse::<u16>).map(|i| i.unwrap());
+        let mut iterator = s.split(',').map(str::parse::<u32>).map(|i| i.unwrap());
         let x = iterator.next().unwrap();
         let y = iterator.next().unwrap();
         match iterator.next() {
@@ -48,40 +50,163 @@ pub mod day_5 {
         parse(include_str!("../input.txt"))
     }
 
-    struct Board<T> {
-        entries: Vec<Option<(T, Option<T>)>>,
-        max_y: usize,
+    impl Line {
+        pub fn new(start: (u32, u32), end: (u32, u32)) -> Line {
+            Line {
+                start: Coordinate {
+                    x: start.0,
+                    y: start.1,
+                },
+                end: Coordinate { x: end.0, y: end.1 },
+            }
+        }
+
+        fn delta(&self) -> (i64, i64) {
+            (
+                self.end.x as i64 - self.start.x as i64,
+                self.end.y as i64 - self.start.y as i64,
+            )
+        }
+
+        // The number of steps between lattice points along the line, and the size of each step.
+        fn steps(&self) -> (i64, (i64, i64)) {
+            let (dx, dy) = self.delta();
+            let steps = gcd(dx.unsigned_abs(), dy.unsigned_abs()) as i64;
+            // A line of one point has no direction; take a single step of nothing.
+            if steps == 0 {
+                (0, (0, 0))
+            } else {
+                (steps, (dx / steps, dy / steps))
+            }
+        }
+
+        /// Every lattice point on the line, including both ends, from start to end.
+        pub fn points(&self) -> impl Iterator<Item = (u32, u32)> {
+            let (steps, (step_x, step_y)) = self.steps();
+            let (x, y) = (self.start.x as i64, self.start.y as i64);
+            (0..=steps).map(move |i| ((x + i * step_x) as u32, (y + i * step_y) as u32))
+        }
+    }
+
+    fn gcd(mut a: u64, mut b: u64) -> u64 {
+        while b != 0 {
+            (a, b) = (b, a % b);
+        }
+        a
+    }
+
+    /// Which lines to draw.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Angles {
+        /// Only horizontal and vertical lines.
+        Orthogonal,
+        /// Horizontal, vertical, and 45-degree lines.
+        Octilinear,
+        /// Every line, at whatever angle.
+        Any,
     }
 
-    impl<T> Board<T> {
-        fn new(max_x: usize, max_y: usize) -> Board<T> {
-            let entries = std::iter::repeat_with(|| None)
-                .take((max_x + 1) * (max_y + 1))
-                .collect();
-            Board { entries, max_y }
+    impl Angles {
+        fn includes(self, line: &Line) -> bool {
+            let (dx, dy) = line.delta();
+            match self {
+                Angles::Orthogonal => dx == 0 || dy == 0,
+                Angles::Octilinear => dx == 0 || dy == 0 || dx.abs() == dy.abs(),
+                Angles::Any => true,
+            }
         }
     }
 
-    fn elt_at<T>(board: &Board<T>, x: usize, y: usize) -> &Option<(T, Option<T>)> {
-        &board.entries[board.max_y * x + y]
+    /// How many lines cover each point of the grid.
+    pub struct Board {
+        counts: Vec<u32>,
+        width: usize,
+        height: usize,
     }
 
-    impl<T> fmt::Display for Board<T> {
+    impl Board {
+        /// Draw the lines which have the given angles.
+        pub fn new(data: &[Line], angles: Angles) -> Board {
+            let width = data
+                .iter()
+                .map(|line| max(line.start.x, line.end.x) as usize + 1)
+                .max()
+                .unwrap_or(0);
+            let height = data
+                .iter()
+                .map(|line| max(line.start.y, line.end.y) as usize + 1)
+                .max()
+                .unwrap_or(0);
+            let mut counts = vec![0; width * height];
+            for line in data.iter().filter(|line| angles.includes(line)) {
+                for (x, y) in line.points() {
+                    counts[y as usize * width + x as usize] += 1;
+                }
+            }
+            Board {
+                counts,
+                width,
+                height,
+            }
+        }
+
+        pub fn width(&self) -> usize {
+            self.width
+        }
+
+        pub fn height(&self) -> usize {
+            self.height
+        }
+
+        /// The number of lines through (x, y); zero if that's off the board.
+        pub fn overlaps(&self, x: usize, y: usize) -> u32 {
+            if x < self.width && y < self.height {
+                self.counts[y * self.width + x]
+            } else {
+                0
+            }
+        }
+
+        /// The number of points covered by at least `k` lines.
+        pub fn at_least(&self, k: u32) -> usize {
+            self.counts.iter().filter(|&&count| count >= k).count()
+        }
+
+        /// Write the board as a binary PPM image, one pixel per point, shaded from black where
+        /// there are no lines through yellow to white at the most overlaps.
+        pub fn write_ppm<W>(&self, mut w: W) -> io::Result<()>
+        where
+            W: io::Write,
+        {
+            write!(w, "P6\n{} {}\n255\n", self.width, self.height)?;
+            let most = self.counts.iter().copied().max().unwrap_or(0).max(1) as u64;
+            let mut pixels = Vec::with_capacity(3 * self.counts.len());
+            for &count in &self.counts {
+                // Red, then green, then blue each ramp up over a third of the range.
+                let heat = 3 * 255 * count as u64 / most;
+                for channel in 0..3 {
+                    pixels.push(heat.saturating_sub(255 * channel).min(255) as u8);
+                }
+            }
+            w.write_all(&pixels)
+        }
+    }
+
+    /// The map in the style of the puzzle: a dot where there are no lines, and otherwise the
+    /// number of lines, or `+` if there are more than nine.
+    impl fmt::Display for Board {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-            for x in 0..=(self.entries.len() / self.max_y) {
-                for y in 0..=self.max_y {
-                    let entry = elt_at(self, x, y);
-                    match *entry {
-                        None => {
-                            write!(f, ".")?;
-                        }
-                        Some((_, None)) => {
-                            write!(f, "1")?;
-                        }
-                        _ => {
-                            write!(f, "2")?;
-                        }
-                    }
+            if self.width == 0 {
+                return fmt::Result::Ok(());
+            }
+            for row in self.counts.chunks(self.width) {
+                for &count in row {
+                    let c = match count {
+                        0 => '.',
+                        1..=9 => char::from_digit(count, 10).unwrap(),
+                        _ => '+',
+                    };
+                    write!(f, "{}", c)?;
                 }
                 writeln!(f)?;
             }
@@ -89,102 +214,143 @@ pub mod day_5 {
         }
     }
 
-    fn elt_at_mut<T>(board: &mut Board<T>, x: usize, y: usize) -> &mut Option<(T, Option<T>)> {
-        &mut board.entries[board.max_y * x + y]
+    // A stretch of lattice points, all covered by the same number of lines, which runs from
+    // `start` in `len` steps of `direction`.
+    struct Run {
+        start: (i64, i64),
+        direction: (i64, i64),
+        len: i64,
+        count: u32,
     }
 
-    fn set_at<T>(board: &mut Board<T>, x: u16, y: u16, new_val: T)
-    where
-        T: Copy,
-    {
-        let entry = elt_at_mut(board, x as usize, y as usize);
-        match *entry {
-            None => {
-                *entry = Some((new_val, None));
+    impl Run {
+        // The lattice point where two runs in different directions cross, if there is one.
+        fn crossing(&self, other: &Run) -> Option<(i64, i64)> {
+            let cross = |(a, b): (i64, i64), (c, d): (i64, i64)| {
+                a as i128 * d as i128 - b as i128 * c as i128
+            };
+            let denominator = cross(self.direction, other.direction);
+            let offset = (other.start.0 - self.start.0, other.start.1 - self.start.1);
+            let t = cross(offset, other.direction);
+            let s = cross(offset, self.direction);
+            if t % denominator != 0 || s % denominator != 0 {
+                return None;
             }
-            Some((existing, None)) => {
-                *entry = Some((existing, Some(new_val)));
+            let (t, s) = (t / denominator, s / denominator);
+            if t < 0 || t > self.len as i128 || s < 0 || s > other.len as i128 {
+                return None;
             }
-            Some((_, Some(_))) => {}
+            let t = t as i64;
+            Some((
+                self.start.0 + t * self.direction.0,
+                self.start.1 + t * self.direction.1,
+            ))
         }
     }
 
-    fn do_it(board: &mut Board<u16>, data: &[Line], enable_diagonal: bool) {
-        for (line_num, line) in data.iter().enumerate() {
-            if line.start.x == line.end.x {
-                let bottom = min(line.start.y, line.end.y);
-                let top = max(line.start.y, line.end.y);
-                let x = line.start.x;
-                for y in bottom..=top {
-                    set_at(board, x, y, line_num as u16);
-                }
-            } else if line.start.y == line.end.y {
-                let y = line.start.y;
-                let bottom = min(line.start.x, line.end.x);
-                let top = max(line.start.x, line.end.x);
-                for x in bottom..=top {
-                    set_at(board, x, y, line_num as u16);
-                }
-            } else if enable_diagonal {
-                let (left_point, right_point) = if line.start.x < line.end.x {
-                    (&line.start, &line.end)
-                } else {
-                    (&line.end, &line.start)
-                };
-
-                if left_point.y < right_point.y {
-                    for i in 0..=right_point.y - left_point.y {
-                        set_at(board, left_point.x + i, left_point.y + i, line_num as u16);
-                    }
-                } else {
-                    for i in 0..=left_point.y - right_point.y {
-                        set_at(board, left_point.x + i, left_point.y - i, line_num as u16);
+    // An infinite line through lattice points: its primitive direction, and its offset from
+    // the parallel line through the origin.
+    type Carrier = ((i64, i64), i128);
+
+    // Merge the lines into runs of constant cover, never materialising any point.
+    // Lines which lie along the same infinite line are swept together in order along it.
+    fn runs<'a, I>(lines: I) -> Vec<Run>
+    where
+        I: Iterator<Item = &'a Line>,
+    {
+        // Directions point rightwards, or up if vertical. Each event is a position along the
+        // line, as the dot product with the direction, and the change in cover there.
+        let mut carriers: HashMap<Carrier, Vec<(i128, i32)>> = HashMap::new();
+        for line in lines {
+            let (steps, (mut a, mut b)) = line.steps();
+            let (mut from, mut to) = (&line.start, &line.end);
+            if steps == 0 {
+                // Any direction will do for a single point.
+                (a, b) = (1, 0);
+            } else if a < 0 || (a == 0 && b < 0) {
+                (a, b) = (-a, -b);
+                (from, to) = (to, from);
+            }
+            let dot = |p: &Coordinate| a as i128 * p.x as i128 + b as i128 * p.y as i128;
+            let offset = b as i128 * from.x as i128 - a as i128 * from.y as i128;
+            let step = (a * a + b * b) as i128;
+            let events = carriers.entry(((a, b), offset)).or_default();
+            events.push((dot(from), 1));
+            events.push((dot(to) + step, -1));
+        }
+
+        let mut runs = Vec::new();
+        for (((a, b), offset), mut events) in carriers {
+            events.sort_unstable();
+            let step = (a * a + b * b) as i128;
+            // The point along this line whose dot product with the direction is `position`.
+            let point = |position: i128| {
+                let x = (position * a as i128 + offset * b as i128) / step;
+                let y = (position * b as i128 - offset * a as i128) / step;
+                (x as i64, y as i64)
+            };
+            let mut count = 0;
+            let mut events = events.into_iter().peekable();
+            while let Some((position, change)) = events.next() {
+                count += change;
+                if let Some(&(next, _)) = events.peek() {
+                    if count > 0 && next > position {
+                        runs.push(Run {
+                            start: point(position),
+                            direction: (a, b),
+                            len: ((next - position) / step - 1) as i64,
+                            count: count as u32,
+                        });
                     }
                 }
             }
         }
+        runs
     }
 
-    fn count_multiple<T>(board: &Board<T>) -> usize {
-        board
-            .entries
+    /// The number of points covered by at least `k` lines (which must be positive), computed
+    /// from where the lines cross rather than by drawing them, so it copes with coordinates
+    /// too spread out to fit a `Board` in memory.
+    /// The time taken is quadratic in the number of lines.
+    pub fn at_least_sparse(data: &[Line], angles: Angles, k: u32) -> usize {
+        assert!(k > 0, "Every point is covered by at least zero lines");
+        let runs = runs(data.iter().filter(|line| angles.includes(line)));
+
+        let mut total: usize = runs
             .iter()
-            .filter(|i| matches!(i, Some((_, Some(_)))))
-            .count()
+            .filter(|run| run.count >= k)
+            .map(|run| run.len as usize + 1)
+            .sum();
+
+        // Where runs cross, the run counts were wrong: fix them up.
+        let mut crossings: BTreeMap<(i64, i64), Vec<usize>> = BTreeMap::new();
+        for (i, first) in runs.iter().enumerate() {
+            for (j, second) in runs.iter().enumerate().skip(i + 1) {
+                if first.direction != second.direction {
+                    if let Some(point) = first.crossing(second) {
+                        let at_point = crossings.entry(point).or_default();
+                        at_point.push(i);
+                        at_point.push(j);
+                    }
+                }
+            }
+        }
+        for (_, mut at_point) in crossings {
+            at_point.sort_unstable();
+            at_point.dedup();
+            let cover: u32 = at_point.iter().map(|&i| runs[i].count).sum();
+            let counted = at_point.iter().filter(|&&i| runs[i].count >= k).count();
+            total = total + (cover >= k) as usize - counted;
+        }
+        total
     }
 
     pub fn part_1(data: &[Line]) -> usize {
-        let max_x = data
-            .iter()
-            .map(|line| max(line.start.x, line.end.x))
-            .max()
-            .unwrap() as usize;
-        let max_y = data
-            .iter()
-            .map(|line| max(line.start.y, line.end.y))
-            .max()
-            .unwrap() as usize;
-        let mut board = Board::new(max_x, max_y);
-
-        do_it(&mut board, data, false);
-        count_multiple(&board)
+        Board::new(data, Angles::Orthogonal).at_least(2)
     }
 
     pub fn part_2(data: &[Line]) -> usize {
-        let max_x = data
-            .iter()
-            .map(|line| max(line.start.x, line.end.x))
-            .max()
-            .unwrap() as usize;
-        let max_y = data
-            .iter()
-            .map(|line| max(line.start.y, line.end.y))
-            .max()
-            .unwrap() as usize;
-        let mut board = Board::new(max_x, max_y);
-
-        do_it(&mut board, data, true);
-        count_multiple(&board)
+        Board::new(data, Angles::Octilinear).at_least(2)
     }
 }
 
@@ -217,6 +383,123 @@ mod tests {
         assert_eq!(part_2(&data), 12);
     }
 
+    #[test]
+    fn points_known() {
+        let points = |s| parse(s)[0].points().collect::<Vec<_>>();
+        assert_eq!(points("1,1 -> 1,3"), vec![(1, 1), (1, 2), (1, 3)]);
+        assert_eq!(points("9,7 -> 7,9"), vec![(9, 7), (8, 8), (7, 9)]);
+        assert_eq!(points("0,0 -> 6,4"), vec![(0, 0), (3, 2), (6, 4)]);
+        assert_eq!(points("7,1 -> 1,3"), vec![(7, 1), (4, 2), (1, 3)]);
+        assert_eq!(points("2,5 -> 3,9"), vec![(2, 5), (3, 9)]);
+        assert_eq!(points("4,4 -> 4,4"), vec![(4, 4)]);
+    }
+
+    #[test]
+    fn overlaps_known() {
+        let data = parse(TEST_INPUT);
+        let board = Board::new(&data, Angles::Octilinear);
+        assert_eq!(board.width(), 10);
+        assert_eq!(board.height(), 10);
+        assert_eq!(board.overlaps(0, 0), 1);
+        assert_eq!(board.overlaps(1, 0), 0);
+        assert_eq!(board.overlaps(4, 4), 3);
+        assert_eq!(board.overlaps(0, 9), 2);
+        assert_eq!(board.overlaps(10, 0), 0);
+        assert_eq!(board.at_least(1), 39);
+        assert_eq!(board.at_least(3), 2);
+
+        let extra = parse(&format!("{}\n0,0 -> 8,4\n4,0 -> 4,9", TEST_INPUT));
+        let board = Board::new(&extra, Angles::Any);
+        // 0,0 -> 8,4 passes through (2, 1), (4, 2) and (6, 3), and nowhere else on the lattice.
+        assert_eq!(board.overlaps(2, 1), 2);
+        assert_eq!(board.overlaps(4, 2), 3);
+        assert_eq!(board.overlaps(6, 3), 1);
+        assert_eq!(board.overlaps(4, 4), 4);
+        assert_eq!(board.at_least(4), 1);
+        assert_eq!(Board::new(&extra, Angles::Octilinear).overlaps(6, 3), 0);
+    }
+
+    #[test]
+    fn display_known() {
+        let data = parse(TEST_INPUT);
+        let expected = "1.1....11.
+.111...2..
+..2.1.111.
+...1.2.2..
+.112313211
+...1.2....
+..1...1...
+.1.....1..
+1.......1.
+222111....
+";
+        assert_eq!(Board::new(&data, Angles::Octilinear).to_string(), expected);
+
+        let data = parse("0,0 -> 2,0\n0,0 -> 0,1\n1,0 -> 1,1");
+        assert_eq!(Board::new(&data, Angles::Any).to_string(), "221\n11.\n");
+    }
+
+    #[test]
+    fn ppm_known() {
+        let data = parse("0,0 -> 2,0\n0,0 -> 0,1\n1,0 -> 1,1");
+        let mut ppm = Vec::new();
+        Board::new(&data, Angles::Any).write_ppm(&mut ppm).unwrap();
+        let (header, pixels) = ppm.split_at(11);
+        assert_eq!(header, b"P6\n3 2\n255\n");
+        let (white, red, black) = ([255, 255, 255], [255, 127, 0], [0, 0, 0]);
+        assert_eq!(pixels, [white, white, red, red, red, black].concat());
+    }
+
+    #[test]
+    fn sparse_known() {
+        let data = parse(TEST_INPUT);
+        assert_eq!(at_least_sparse(&data, Angles::Orthogonal, 2), 5);
+        assert_eq!(at_least_sparse(&data, Angles::Octilinear, 2), 12);
+        assert_eq!(at_least_sparse(&data, Angles::Octilinear, 1), 39);
+
+        let far = 4_000_000_000;
+        let data = vec![
+            Line::new((0, 0), (far, far)),
+            Line::new((0, far), (far, 0)),
+            Line::new((0, 2_000_000_000), (far, 2_000_000_000)),
+            Line::new(
+                (1_000_000_000, 1_000_000_000),
+                (3_000_000_000, 3_000_000_000),
+            ),
+            Line::new((far, 0), (far, 0)),
+        ];
+        // The second diagonal, and all four lines through the middle.
+        assert_eq!(at_least_sparse(&data, Angles::Any, 2), 2_000_000_002);
+        assert_eq!(at_least_sparse(&data, Angles::Any, 3), 1);
+        assert_eq!(at_least_sparse(&data, Angles::Any, 4), 1);
+        assert_eq!(at_least_sparse(&data, Angles::Any, 5), 0);
+        assert_eq!(
+            at_least_sparse(&data, Angles::Orthogonal, 1),
+            far as usize + 2
+        );
+    }
+
+    // The dense board is the obviously-correct way round.
+    #[test]
+    fn sparse_agrees_with_board() {
+        let mut data = input();
+        let extra = "0,0 -> 8,4
+8,4 -> 16,8
+3,0 -> 0,9
+10,2 -> 2,10
+5,5 -> 5,5
+1,1 -> 7,19
+7,19 -> 1,1
+0,3 -> 9,0";
+        data.extend(parse(extra));
+        for angles in [Angles::Orthogonal, Angles::Octilinear, Angles::Any] {
+            let board = Board::new(&data, angles);
+            for k in 1..=4 {
+                assert_eq!(at_least_sparse(&data, angles, k), board.at_least(k));
+            }
+        }
+    }
+
     #[test]
     fn test_day_5() {
         let input = input();
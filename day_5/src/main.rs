@@ -1,7 +1,20 @@
-use day_5::day_5;
+use day_5::day_5::{input, part_1, part_2, Angles, Board};
+use std::fs::File;
+use std::io::BufWriter;
 
 fn main() {
-    let input = day_5::input();
-    println!("part 1 => {}", day_5::part_1(&input));
-    println!("part 2 => {}", day_5::part_2(&input));
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let input = input();
+    match args.as_slice() {
+        [command, path] if command == "render" => {
+            let file = File::create(path).expect("Could not create output file");
+            Board::new(&input, Angles::Octilinear)
+                .write_ppm(BufWriter::new(file))
+                .expect("Could not write image");
+        }
+        _ => {
+            println!("part 1 => {}", part_1(&input));
+            println!("part 2 => {}", part_2(&input));
+        }
+    }
 }
@@ -1,5 +1,9 @@
 pub mod day_6 {
 
+    pub use ::matrix::matrix::Arithmetic;
+    use ::matrix::matrix::Matrix;
+    use std::cmp::max;
+
     pub(crate) fn parse(s: &str) -> Vec<u8> {
         s.trim()
             .split(',')
@@ -12,70 +16,109 @@ pub mod day_6 {
         parse(include_str!("../input.txt"))
     }
 
-    fn tick(data: &mut Vec<u8>) {
-        let mut new_fish = Vec::new();
-        for fish in data.iter_mut() {
-            if *fish == 0 {
-                *fish = 6;
-                new_fish.push(8);
-            } else {
-                *fish -= 1;
-            }
-        }
-        data.extend(new_fish);
+    /// A fish whose timer is at 0 spawns a new fish, and its timer restarts at `cycle - 1`;
+    /// the new fish's timer starts at `first_delay - 1`. Every other timer counts down by one a day.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct LanternfishModel {
+        pub cycle: usize,
+        pub first_delay: usize,
     }
 
-    pub(crate) fn execute(data: &[u8], count: u8) -> u64 {
-        let mut data = data.to_vec();
-        for _ in 0..count {
-            tick(&mut data);
+    impl LanternfishModel {
+        pub const PUZZLE: LanternfishModel = LanternfishModel {
+            cycle: 7,
+            first_delay: 9,
+        };
+
+        /// Timers run from 0 up to (but excluding) this.
+        fn timers(&self) -> usize {
+            assert!(
+                self.cycle > 0 && self.first_delay > 0,
+                "Fish must wait at least a day to spawn, got {:?}",
+                self
+            );
+            max(self.cycle, self.first_delay)
         }
 
-        data.len() as u64
-    }
+        /// How many fish there are with each timer value.
+        fn buckets(&self, fish: &[u8]) -> Vec<u64> {
+            let mut buckets = vec![0; self.timers()];
+            for &timer in fish {
+                match buckets.get_mut(timer as usize) {
+                    None => panic!("Timer {} is out of range for {:?}", timer, self),
+                    Some(count) => *count += 1,
+                }
+            }
+            buckets
+        }
 
-    pub fn part_1(data: &[u8]) -> u64 {
-        execute(data, 80)
-    }
+        /// The population after the given number of days, a day at a time.
+        /// Panics if the population overflows a `u64`.
+        pub fn simulate(&self, fish: &[u8], days: u64) -> u64 {
+            let mut buckets = self.buckets(fish);
+            for _ in 0..days {
+                let spawning = buckets[0];
+                buckets.rotate_left(1);
+                // The rotation has already put the parents at the back of the queue.
+                let back = buckets.len() - 1;
+                buckets[back] = 0;
+                for timer in [self.cycle - 1, self.first_delay - 1] {
+                    buckets[timer] = buckets[timer]
+                        .checked_add(spawning)
+                        .expect("Population overflowed a u64; use population_pow");
+                }
+            }
+            buckets
+                .iter()
+                .try_fold(0u64, |total, &count| total.checked_add(count))
+                .expect("Population overflowed a u64; use population_pow")
+        }
+
+        /// The one-day transition matrix: entry (s, t) is how many fish with timer `s` a single
+        /// fish with timer `t` becomes.
+        fn transition_matrix(&self) -> Matrix {
+            let size = self.timers();
+            let mut elts = vec![0; size * size];
+            for t in 1..size {
+                elts[(t - 1) * size + t] = 1;
+            }
+            elts[(self.cycle - 1) * size] += 1;
+            elts[(self.first_delay - 1) * size] += 1;
+            Matrix::make(size, elts)
+        }
 
-    fn fish_created_given_spawning(fish_cache: &mut [u64], days: u16) -> u64 {
-        let possible_result = fish_cache[days as usize];
-        if possible_result != 0 {
-            return possible_result;
+        /// The population after the given number of days, via `Matrix::apply_pow` on the
+        /// transition matrix.
+        /// Returns `None` if the population overflows under `Arithmetic::Checked`.
+        pub fn population_pow(
+            &self,
+            fish: &[u8],
+            days: u64,
+            arithmetic: Arithmetic,
+        ) -> Option<u128> {
+            let buckets: Vec<u128> = self.buckets(fish).iter().map(|&x| x as u128).collect();
+            let result = self
+                .transition_matrix()
+                .apply_pow(&buckets, days, arithmetic)?;
+            result
+                .into_iter()
+                .try_fold(0, |total, count| arithmetic.add(total, count))
         }
-        let result = if days > 9 {
-            // We start spawning later...
-            fish_created_given_spawning(fish_cache, days - 9)
-            // and we create a fish now, which spawns eventually...
-                + fish_created_given_spawning(fish_cache, days - 7)
-        } else if days > 7 {
-            // In principle this could be tail-called, but I can't be bothered.
-            fish_created_given_spawning(fish_cache, days - 7) + 1
-        } else {
-            2
-        };
-        fish_cache[days as usize] = result;
-        result
     }
 
-    fn fish_created(fish_cache: &mut [u64], start: u8, days: u16) -> u64 {
-        if days == 0 {
-            return 1;
-        }
-        fish_created_given_spawning(fish_cache, days - start as u16)
+    pub fn part_1(data: &[u8]) -> u64 {
+        LanternfishModel::PUZZLE.simulate(data, 80)
     }
 
     pub fn part_2(data: &[u8]) -> u64 {
-        let mut fish_cache = vec![0; 257];
-        data.iter()
-            .map(|i| fish_created(&mut fish_cache, *i, 256))
-            .sum()
+        LanternfishModel::PUZZLE.simulate(data, 256)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::day_6::*;
+    use std::num::NonZeroU64;
 
     static TEST_INPUT: &str = "3,4,3,1,2";
 
@@ -83,7 +126,71 @@ mod tests {
     fn part1_known() {
         let data = parse(TEST_INPUT);
 
-        assert_eq!(execute(&data, 18), 26);
+        assert_eq!(LanternfishModel::PUZZLE.simulate(&data, 18), 26);
+        assert_eq!(part_1(&data), 5934);
+    }
+
+    #[test]
+    fn population_pow_known() {
+        let data = parse(TEST_INPUT);
+        let model = LanternfishModel::PUZZLE;
+        for days in [0, 1, 18, 80, 256] {
+            assert_eq!(
+                model.population_pow(&data, days, Arithmetic::Checked),
+                Some(model.simulate(&data, days) as u128)
+            );
+        }
+
+        let modulus = NonZeroU64::new(1_000_000_007).unwrap();
+        let exact = model
+            .population_pow(&data, 700, Arithmetic::Checked)
+            .unwrap();
+        assert_eq!(
+            model.population_pow(&data, 700, Arithmetic::Modulo(modulus)),
+            Some(exact % modulus.get() as u128)
+        );
+        assert_eq!(model.population_pow(&data, 2000, Arithmetic::Checked), None);
+        assert!(model
+            .population_pow(&data, u64::MAX, Arithmetic::Modulo(modulus))
+            .is_some());
+    }
+
+    #[test]
+    fn other_models() {
+        // Every fish spawns every day, so the population doubles daily.
+        let doubling = LanternfishModel {
+            cycle: 1,
+            first_delay: 1,
+        };
+        assert_eq!(doubling.simulate(&[0, 0, 0], 10), 3 * 1024);
+        assert_eq!(
+            doubling.population_pow(&[0, 0, 0], 100, Arithmetic::Checked),
+            Some(3 << 100)
+        );
+
+        // Newborns spawn sooner than their parents do again.
+        let model = LanternfishModel {
+            cycle: 3,
+            first_delay: 2,
+        };
+        for days in 0..30 {
+            assert_eq!(
+                model.population_pow(&[2, 0], days, Arithmetic::Checked),
+                Some(model.simulate(&[2, 0], days) as u128)
+            );
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "Population overflowed a u64; use population_pow")]
+    fn simulate_overflows() {
+        // The population grows like the Fibonacci numbers. After 92 days each bucket still fits
+        // in a u64, but the two of them together don't.
+        let fibonacci = LanternfishModel {
+            cycle: 1,
+            first_delay: 2,
+        };
+        fibonacci.simulate(&[0], 92);
     }
 
     #[test]
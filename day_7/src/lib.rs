@@ -1,13 +1,5 @@
 pub mod day_7 {
 
-    const fn difference(a: u16, b: u16) -> u16 {
-        if a >= b {
-            a - b
-        } else {
-            b - a
-        }
-    }
-
     pub(crate) fn parse(s: &str) -> Vec<u16> {
         s.trim()
             .split(',')
@@ -20,22 +12,6 @@ pub mod day_7 {
         parse(include_str!("../input.txt"))
     }
 
-    fn min_convex<I, T>(mut data: I) -> Option<T>
-    where
-        I: Iterator<Item = T>,
-        T: Ord,
-    {
-        let mut fst = data.next()?;
-        for next in data {
-            if fst > next {
-                fst = next;
-            } else {
-                return Some(fst);
-            };
-        }
-        Some(fst)
-    }
-
     fn min_max<I, T>(mut data: I) -> Option<(T, T)>
     where
         I: Iterator<Item = T>,
@@ -53,24 +29,145 @@ pub mod day_7 {
         Some((min, max))
     }
 
-    pub fn part_1(data: &[u16]) -> u32 {
-        let (&min, &max) = min_max(data.iter()).unwrap();
-        min_convex((min..=max).map(|i| data.iter().map(|value| difference(i, *value) as u32).sum()))
-            .unwrap()
+    /// The fuel a crab burns to move a given distance.
+    pub trait FuelCost {
+        /// Must be convex in `distance`, or `optimum` may not find the best position.
+        fn cost(&self, distance: u32) -> u64;
+
+        /// The total fuel for every crab to move to `position`.
+        fn total(&self, crabs: &[u16], position: u16) -> u64 {
+            crabs
+                .iter()
+                .map(|&crab| self.cost(crab.abs_diff(position) as u32))
+                .sum()
+        }
+
+        /// The leftmost of the cheapest positions to align at, with the fuel it takes, or
+        /// `None` if there are no crabs.
+        /// Between neighbouring crabs, every crab's distance changes linearly, so the total is
+        /// convex there even if `cost` isn't increasing. By default each of those stretches,
+        /// and those beyond the outermost crabs, is ternary searched.
+        fn optimum(&self, crabs: &[u16]) -> Option<(u16, u64)> {
+            if crabs.is_empty() {
+                return None;
+            }
+            let mut bounds = crabs.to_vec();
+            bounds.extend([0, u16::MAX]);
+            bounds.sort_unstable();
+            bounds.dedup();
+            bounds
+                .windows(2)
+                .map(|stretch| convex_minimum(self, crabs, stretch[0], stretch[1]))
+                .min_by_key(|&(position, fuel)| (fuel, position))
+        }
     }
 
-    const fn triangle(i: u32) -> u32 {
-        i * (i + 1) / 2
+    // The leftmost cheapest position between `from` and `to`, where the total is convex: a
+    // ternary search, then a bisection for the left end of any flat run it lands in.
+    fn convex_minimum<C>(cost: &C, crabs: &[u16], from: u16, to: u16) -> (u16, u64)
+    where
+        C: FuelCost + ?Sized,
+    {
+        let (mut lo, mut hi) = (from, to);
+        while hi - lo > 2 {
+            let m1 = lo + (hi - lo) / 3;
+            let m2 = hi - (hi - lo) / 3;
+            let (f1, f2) = (cost.total(crabs, m1), cost.total(crabs, m2));
+            // By convexity, a minimum lies on the cheaper side; and if neither side is
+            // cheaper, one lies between them.
+            if f1 <= f2 {
+                hi = if f1 < f2 { m2 - 1 } else { m2 };
+            }
+            if f1 >= f2 {
+                lo = m1;
+            }
+        }
+        let (position, fuel) = best_of(cost, crabs, lo..=hi).unwrap();
+        // By convexity, the total never rises on the way from `from` to `position`, so the
+        // positions as cheap form a run ending there.
+        let (mut lo, mut hi) = (from, position);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            if cost.total(crabs, mid) == fuel {
+                hi = mid;
+            } else {
+                lo = mid + 1;
+            }
+        }
+        (hi, fuel)
     }
 
-    pub fn part_2(data: &[u16]) -> u32 {
-        let (&min, &max) = min_max(data.iter()).unwrap();
-        min_convex((min..=max).map(|i| {
-            data.iter()
-                .map(|value| triangle(difference(i, *value) as u32))
-                .sum()
-        }))
-        .unwrap()
+    // The leftmost cheapest of the given positions.
+    fn best_of<C, I>(cost: &C, crabs: &[u16], positions: I) -> Option<(u16, u64)>
+    where
+        C: FuelCost + ?Sized,
+        I: Iterator<Item = u16>,
+    {
+        positions
+            .map(|position| (position, cost.total(crabs, position)))
+            .min_by_key(|&(position, fuel)| (fuel, position))
+    }
+
+    /// Each step costs one unit of fuel. The best position is a median.
+    pub struct Linear;
+
+    impl FuelCost for Linear {
+        fn cost(&self, distance: u32) -> u64 {
+            distance as u64
+        }
+
+        fn optimum(&self, crabs: &[u16]) -> Option<(u16, u64)> {
+            if crabs.is_empty() {
+                return None;
+            }
+            let mut sorted = crabs.to_vec();
+            sorted.sort_unstable();
+            // With an even number of crabs, everywhere between the two middle ones is equally
+            // good, so the lower of them is the leftmost.
+            let median = sorted[(sorted.len() - 1) / 2];
+            Some((median, self.total(crabs, median)))
+        }
+    }
+
+    /// Each step costs one more than the last. The best position is within a half of the mean.
+    pub struct Triangular;
+
+    impl FuelCost for Triangular {
+        fn cost(&self, distance: u32) -> u64 {
+            let distance = distance as u64;
+            distance * (distance + 1) / 2
+        }
+
+        fn optimum(&self, crabs: &[u16]) -> Option<(u16, u64)> {
+            let (&min, &max) = min_max(crabs.iter())?;
+            let sum: u64 = crabs.iter().map(|&crab| crab as u64).sum();
+            let mean = (sum / crabs.len() as u64) as u16;
+            // The real minimum is within a half of the mean, so the best integer is within one
+            // of it, and the mean rounds down to at most one below that.
+            let from = std::cmp::max(min, mean.saturating_sub(1));
+            let to = std::cmp::min(max, mean.saturating_add(2));
+            best_of(self, crabs, from..=to)
+        }
+    }
+
+    /// Any convex cost function, with the optimum found by ternary search.
+    pub struct Convex<F>(pub F);
+
+    impl<F> FuelCost for Convex<F>
+    where
+        F: Fn(u32) -> u64,
+    {
+        fn cost(&self, distance: u32) -> u64 {
+            (self.0)(distance)
+        }
+    }
+
+    pub fn part_1(data: &[u16]) -> u64 {
+        Linear.optimum(data).unwrap().1
+    }
+
+    pub fn part_2(data: &[u16]) -> u64 {
+        Triangular.optimum(data).unwrap().1
     }
 }
 
@@ -94,6 +191,67 @@ mod tests {
         assert_eq!(part_2(&data), 168);
     }
 
+    #[test]
+    fn optimum_known() {
+        let data = parse(TEST_INPUT);
+        assert_eq!(Linear.optimum(&data), Some((2, 37)));
+        assert_eq!(Linear.total(&data, 1), 41);
+        assert_eq!(Linear.total(&data, 3), 39);
+        assert_eq!(Linear.total(&data, 10), 71);
+        assert_eq!(Triangular.optimum(&data), Some((5, 168)));
+        assert_eq!(Triangular.total(&data, 2), 206);
+        assert_eq!(Linear.optimum(&[]), None);
+    }
+
+    // Check the closed forms and the ternary search against trying every position.
+    #[test]
+    fn optimum_agrees_with_exhaustive() {
+        fn exhaustive<C: FuelCost>(cost: &C, crabs: &[u16]) -> (u16, u64) {
+            (0..=u16::MAX)
+                .map(|position| (position, cost.total(crabs, position)))
+                .min_by_key(|&(position, fuel)| (fuel, position))
+                .unwrap()
+        }
+        let cases: [&[u16]; 6] = [
+            &[16, 1, 2, 0, 4, 2, 7, 1, 2, 14],
+            &[5],
+            &[0, 10],
+            &[3, 3, 3, 100],
+            &[0, 0, 0, 1, 1000],
+            &[7, 2, 9, 9, 1, 0, 30, 30, 31],
+        ];
+        let quadratic = Convex(|d: u32| (d as u64) * (d as u64));
+        let flat_then_linear = Convex(|d: u32| d.saturating_sub(3) as u64);
+        // Convex, but cheapest at a distance of 3, so a single crab is best left alone by 3.
+        let at_three = Convex(|d: u32| (d as u64).abs_diff(3).pow(2));
+        for crabs in cases {
+            assert_eq!(Linear.optimum(crabs), Some(exhaustive(&Linear, crabs)));
+            assert_eq!(
+                Triangular.optimum(crabs),
+                Some(exhaustive(&Triangular, crabs))
+            );
+            assert_eq!(
+                quadratic.optimum(crabs),
+                Some(exhaustive(&quadratic, crabs))
+            );
+            assert_eq!(
+                flat_then_linear.optimum(crabs),
+                Some(exhaustive(&flat_then_linear, crabs))
+            );
+            assert_eq!(at_three.optimum(crabs), Some(exhaustive(&at_three, crabs)));
+        }
+        assert_eq!(at_three.optimum(&[10]), Some((7, 0)));
+        let input = input();
+        assert_eq!(
+            Convex(|d| d as u64).optimum(&input).unwrap().1,
+            part_1(&input)
+        );
+        assert_eq!(
+            Convex(|d| Triangular.cost(d)).optimum(&input).unwrap().1,
+            part_2(&input)
+        );
+    }
+
     #[test]
     fn test_day_7() {
         let input = input();
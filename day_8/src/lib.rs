@@ -1,8 +1,36 @@
 pub mod day_8 {
 
-    #[derive(Copy, Clone, Debug)]
+    use std::collections::HashMap;
+    use std::fmt;
+
+    // Segments are named by letters.
+    const MAX_SEGMENTS: u32 = 26;
+
+    /// The lit segments of a display, as a bitmask: bit 0 is 'a', bit 1 is 'b', and so on.
+    #[derive(Copy, Clone, Debug, PartialEq, Eq)]
     pub struct Digit {
-        pub(crate) segments: [bool; 7],
+        pub(crate) segments: u32,
+    }
+
+    impl Digit {
+        fn count_on(self) -> u32 {
+            self.segments.count_ones()
+        }
+
+        fn overlap(self, other: Digit) -> u32 {
+            (self.segments & other.segments).count_ones()
+        }
+    }
+
+    impl fmt::Display for Digit {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            for i in 0..MAX_SEGMENTS {
+                if self.segments & (1 << i) != 0 {
+                    write!(f, "{}", (b'a' + i as u8) as char)?;
+                }
+            }
+            fmt::Result::Ok(())
+        }
     }
 
     #[derive(Debug)]
@@ -11,16 +39,16 @@ pub mod day_8 {
         current: [Digit; 4],
     }
 
-    fn parse_digit(s: &str) -> Digit {
-        let mut answer = [false; 7];
+    pub(crate) fn parse_digit(s: &str) -> Digit {
+        let mut segments = 0;
         for c in s.chars() {
-            if ('a'..='g').contains(&c) {
-                answer[c as usize - 97] = true;
+            if c.is_ascii_lowercase() {
+                segments |= 1 << (c as usize - 97);
             } else {
                 panic!("Unexpected char: {} in {}", c, s);
             }
         }
-        Digit { segments: answer }
+        Digit { segments }
     }
 
     fn parse_row(s: &str) -> Data {
@@ -53,11 +81,6 @@ pub mod day_8 {
         parse(include_str!("../input.txt"))
     }
 
-    fn count_on(d: &Digit) -> u32 {
-        let answer = d.segments.iter().filter(|&i| *i).count() as u32;
-        answer
-    }
-
     fn is_easy_count(i: u32) -> bool {
         i == 2 || i == 3 || i == 4 || i == 7
     }
@@ -67,192 +90,398 @@ pub mod day_8 {
             .map::<u32, _>(|line| {
                 line.current
                     .iter()
-                    .filter(|i| is_easy_count(count_on(i)))
+                    .filter(|i| is_easy_count(i.count_on()))
                     .count() as u32
             })
             .sum()
     }
 
-    pub(crate) fn equal<T>(a1: &[T], a2: &[T]) -> bool
-    where
-        T: Eq,
-    {
-        for (i, v1) in a1.iter().enumerate() {
-            if *v1 != a2[i] {
-                return false;
-            }
+    /// The segments lit for each of 0 to 9 on a correctly-wired display.
+    static FONT: [&str; 10] = [
+        "abcefg", "cf", "acdeg", "acdfg", "bcdf", "abdfg", "abdefg", "acf", "abcdefg", "abcdfg",
+    ];
+
+    // The digits which are the only ones with their number of segments lit, so they can be
+    // picked out however the wires are crossed.
+    const REFERENCES: [u8; 3] = [1, 4, 7];
+
+    // What's left of a digit when the wires are crossed: how many segments it has lit, and how
+    // many of those it shares with each of the reference digits.
+    type Fingerprint = (u32, [u32; 3]);
+
+    fn fingerprint(digit: Digit, references: &[Digit; 3]) -> Fingerprint {
+        (digit.count_on(), references.map(|r| digit.overlap(r)))
+    }
+
+    // The fingerprint of each digit, which are all different.
+    fn fingerprint_table() -> [(Fingerprint, u8); 10] {
+        let font = FONT.map(parse_digit);
+        let references = REFERENCES.map(|r| font[r as usize]);
+        let mut table = [((0, [0; 3]), 0); 10];
+        for (digit, entry) in table.iter_mut().enumerate() {
+            *entry = (fingerprint(font[digit], &references), digit as u8);
         }
-        true
+        table
     }
 
-    pub(crate) fn translate(identification: &[u8; 7], d: &Digit) -> Option<u8> {
-        let mut unshuffled = [false; 7];
-        for (after_shuffle, before_shuffle) in identification.iter().enumerate() {
-            // (0, 2); (1, 5); ...
-            unshuffled[*before_shuffle as usize] = d.segments[after_shuffle];
+    /// Why a line couldn't be decoded.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum DecodeError {
+        /// The digit wasn't seen, so the others can't be told apart.
+        MissingReference { digit: u8 },
+        /// Different patterns were seen for the same digit.
+        Conflicting {
+            digit: u8,
+            first: Digit,
+            second: Digit,
+        },
+        /// No digit could look like this, however the wires are crossed.
+        Unrecognised { pattern: Digit },
+    }
+
+    // The pattern which must be the given reference digit, by its count of lit segments.
+    fn find_reference(line: &Data, font: &[Digit; 10], digit: u8) -> Result<Digit, DecodeError> {
+        let count = font[digit as usize].count_on();
+        let mut candidates = line.seen.iter().filter(|d| d.count_on() == count);
+        let first = *candidates
+            .next()
+            .ok_or(DecodeError::MissingReference { digit })?;
+        match candidates.find(|&&d| d != first) {
+            None => Ok(first),
+            Some(&second) => Err(DecodeError::Conflicting {
+                digit,
+                first,
+                second,
+            }),
         }
+    }
 
-        if !unshuffled[0] {
-            // 1 or 4
-            if !unshuffled[1] {
-                if equal(&unshuffled[2..7], &[true, false, false, true, false]) {
-                    return Some(1);
-                }
-                return None;
-            }
-            if equal(&unshuffled[2..7], &[true, true, false, true, false]) {
-                return Some(4);
+    /// Why a font can't be used.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum FontError {
+        TooManySegments {
+            segments: u32,
+        },
+        UnknownSegment {
+            glyph: usize,
+            segment: char,
+        },
+        DuplicateGlyph {
+            first: usize,
+            second: usize,
+        },
+        /// Some wiring shows the font as itself with these glyphs moved, so even seeing all of
+        /// them can't tell which is which.
+        NotIdentifiable {
+            glyphs: Vec<usize>,
+        },
+    }
+
+    /// The glyphs a display can show, by which segments are lit for each.
+    #[derive(Debug, Clone)]
+    pub struct SegmentFont {
+        segments: u32,
+        glyphs: Vec<Digit>,
+    }
+
+    /// Why a line couldn't be decoded against a `SegmentFont`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum FontDecodeError {
+        /// Not enough was seen to tell which glyph this is.
+        Ambiguous { pattern: Digit },
+        /// Different patterns were seen for the same glyph.
+        Conflicting {
+            glyph: usize,
+            first: Digit,
+            second: Digit,
+        },
+        /// No glyph could look like this, however the wires are crossed.
+        Unrecognised { pattern: Digit },
+    }
+
+    // Which segments of a font can be crossed onto which wires of a line: a wiring must take
+    // each segment to a wire in the class of the same number.
+    #[derive(Clone)]
+    struct Classes {
+        segments: Vec<u32>,
+        wires: Vec<u32>,
+    }
+
+    impl Classes {
+        fn new(segments: u32) -> Classes {
+            Classes {
+                segments: vec![0; segments as usize],
+                wires: vec![0; segments as usize],
             }
-            return None;
         }
 
-        if !unshuffled[1] {
-            // 2, 3, 7
-            if !unshuffled[2] {
-                return None;
-            }
-            if !unshuffled[3] {
-                // 7 only
-                if equal(&unshuffled[4..7], &[false, true, false]) {
-                    return Some(7);
+        // Split each class into what's lit in the glyph and the pattern and what isn't, as
+        // showing the glyph as the pattern requires; or None if no wiring is left that can.
+        fn refine(&self, glyph: Digit, pattern: Digit) -> Option<Classes> {
+            let mut ids: HashMap<(u32, bool), u32> = HashMap::new();
+            let mut sizes: Vec<i32> = Vec::new();
+            let mut split = |classes: &[u32], lit: Digit, count: i32| -> Vec<u32> {
+                classes
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &class)| {
+                        let key = (class, lit.segments & (1 << i) != 0);
+                        let next = ids.len() as u32;
+                        let id = *ids.entry(key).or_insert(next);
+                        if id == next {
+                            sizes.push(0);
+                        }
+                        sizes[id as usize] += count;
+                        id
+                    })
+                    .collect()
+            };
+            let refined = Classes {
+                segments: split(&self.segments, glyph, 1),
+                wires: split(&self.wires, pattern, -1),
+            };
+            sizes.iter().all(|&size| size == 0).then_some(refined)
+        }
+    }
+
+    // A depth-first search for a glyph for each pattern, such that one wiring shows them all.
+    struct Search<'a> {
+        font: &'a SegmentFont,
+        patterns: &'a [Digit],
+        candidates: Vec<Vec<usize>>,
+        glyphs: Vec<usize>,
+        used: Vec<bool>,
+    }
+
+    impl Search<'_> {
+        fn run(&mut self, order: &[usize], classes: &Classes) -> bool {
+            let Some((&i, rest)) = order.split_first() else {
+                return true;
+            };
+            for c in 0..self.candidates[i].len() {
+                let glyph = self.candidates[i][c];
+                if self.used[glyph] {
+                    continue;
                 }
-                return None;
-            }
-            if unshuffled[4] {
-                // 2
-                if equal(&unshuffled[5..7], &[false, true]) {
-                    return Some(2);
+                if let Some(refined) = classes.refine(self.font.glyphs[glyph], self.patterns[i]) {
+                    self.used[glyph] = true;
+                    self.glyphs[i] = glyph;
+                    if self.run(rest, &refined) {
+                        return true;
+                    }
+                    self.used[glyph] = false;
                 }
-                return None;
-            }
-
-            if equal(&unshuffled[5..7], &[true, true]) {
-                return Some(3);
             }
-            return None;
+            false
         }
+    }
 
-        if !unshuffled[2] {
-            // 5, 6
-            if !unshuffled[3] {
-                return None;
+    impl SegmentFont {
+        /// A font on a display of `segments` segments named from 'a', with the glyphs given by
+        /// their lit segments.
+        /// It must be possible to tell which glyph is which, after the wires are crossed, by
+        /// seeing all of them; this is checked by searching for a wiring that shows the font as
+        /// itself with some glyphs moved.
+        pub fn new(segments: u32, glyphs: &[&str]) -> Result<SegmentFont, FontError> {
+            if segments > MAX_SEGMENTS {
+                return Err(FontError::TooManySegments { segments });
             }
-            if unshuffled[4] {
-                if equal(&unshuffled[5..7], &[true, true]) {
-                    return Some(6);
-                } else {
-                    return None;
+            for (glyph, s) in glyphs.iter().enumerate() {
+                if let Some(segment) = s
+                    .chars()
+                    .find(|&c| !c.is_ascii_lowercase() || c as u32 - 'a' as u32 >= segments)
+                {
+                    return Err(FontError::UnknownSegment { glyph, segment });
                 }
             }
-            if equal(&unshuffled[5..7], &[true, true]) {
-                return Some(5);
+            let font = SegmentFont {
+                segments,
+                glyphs: glyphs.iter().map(|s| parse_digit(s)).collect(),
+            };
+            for (second, glyph) in font.glyphs.iter().enumerate() {
+                if let Some(first) = font.glyphs[..second].iter().position(|g| g == glyph) {
+                    return Err(FontError::DuplicateGlyph { first, second });
+                }
             }
-            return None;
-        }
 
-        if !unshuffled[3] {
-            // 0 only
-            if equal(&unshuffled[4..7], &[true, true, true]) {
-                return Some(0);
+            let itself: Vec<usize> = (0..font.glyphs.len()).collect();
+            let unidentified: Vec<usize> = (0..font.glyphs.len())
+                .filter(|&glyph| font.could_be_other(&font.glyphs, &itself, glyph))
+                .collect();
+            if unidentified.is_empty() {
+                Ok(font)
+            } else {
+                Err(FontError::NotIdentifiable {
+                    glyphs: unidentified,
+                })
             }
-            return None;
         }
 
-        if !unshuffled[4] {
-            if equal(&unshuffled[5..7], &[true, true]) {
-                return Some(9);
-            }
-            return None;
+        /// The digits 0 to 9 on a seven-segment display, as in the puzzle.
+        pub fn decimal() -> SegmentFont {
+            SegmentFont::new(7, &FONT).unwrap()
         }
 
-        if equal(&unshuffled[5..7], &[true, true]) {
-            return Some(8);
+        /// The digits 0 to 9 and then A, b, C, d, E, F on a seven-segment display.
+        pub fn hexadecimal() -> SegmentFont {
+            SegmentFont::new(
+                7,
+                &[
+                    "abcefg", "cf", "acdeg", "acdfg", "bcdf", "abdfg", "abdefg", "acf", "abcdefg",
+                    "abcdfg", "abcdef", "bdefg", "abeg", "cdefg", "abdeg", "abde",
+                ],
+            )
+            .unwrap()
         }
 
-        None
-    }
+        pub fn glyphs(&self) -> &[Digit] {
+            &self.glyphs
+        }
 
-    fn last_decreasing<const N: usize>(d: &[u8; N]) -> Option<usize> {
-        (0..N - 1).rev().find(|&i| d[i + 1] > d[i])
-    }
+        // A glyph for each of the distinct patterns, such that one wiring shows them all, with
+        // `forced` pinning one pattern to a glyph.
+        // The patterns with the fewest glyphs they could be are tried first.
+        fn solve(&self, patterns: &[Digit], forced: Option<(usize, usize)>) -> Option<Vec<usize>> {
+            let candidates: Vec<Vec<usize>> = patterns
+                .iter()
+                .enumerate()
+                .map(|(i, pattern)| match forced {
+                    Some((j, glyph)) if j == i => vec![glyph],
+                    _ => (0..self.glyphs.len())
+                        .filter(|&g| self.glyphs[g].count_on() == pattern.count_on())
+                        .collect(),
+                })
+                .collect();
+            let mut order: Vec<usize> = (0..patterns.len()).collect();
+            order.sort_by_key(|&i| candidates[i].len());
+            let mut search = Search {
+                font: self,
+                patterns,
+                candidates,
+                glyphs: vec![0; patterns.len()],
+                used: vec![false; self.glyphs.len()],
+            };
+            search
+                .run(&order, &Classes::new(self.segments))
+                .then_some(search.glyphs)
+        }
 
-    fn reverse<T, const N: usize>(d: &mut [T; N], i: usize)
-    where
-        T: Copy,
-    {
-        let mut x = i;
-        let mut y = d.len() - 1;
-        while x < y {
-            d.swap(x, y);
-            x += 1;
-            y -= 1;
+        // Whether some other wiring shows the patterns with pattern `i` as a different glyph
+        // from the one in `solution`.
+        fn could_be_other(&self, patterns: &[Digit], solution: &[usize], i: usize) -> bool {
+            (0..self.glyphs.len()).any(|glyph| {
+                glyph != solution[i]
+                    && self.glyphs[glyph].count_on() == patterns[i].count_on()
+                    && self.solve(patterns, Some((i, glyph))).is_some()
+            })
         }
-    }
 
-    pub(crate) fn next_perm<const N: usize>(d: &mut [u8; N]) -> Option<()> {
-        // First decreasing element
-        let last_decreasing = last_decreasing(d)?;
-        let elt = d[last_decreasing];
-        let incremented = match (last_decreasing + 1..d.len()).rev().find(|i| d[*i] > elt) {
-            Some(incremented) => incremented,
-            None => {
-                panic!("Unexpected ");
+        // Why no wiring shows all the patterns: the first pattern that can't be shown alongside
+        // those before it is either a glyph that was already shown differently, or nothing.
+        fn diagnose(&self, patterns: &[Digit]) -> FontDecodeError {
+            let k = (1..=patterns.len())
+                .find(|&k| self.solve(&patterns[..k], None).is_none())
+                .expect("all patterns can be shown");
+            let (before, second) = (&patterns[..k - 1], patterns[k - 1]);
+            let solution = self.solve(before, None).unwrap();
+            for (j, &first) in before.iter().enumerate() {
+                let glyph = solution[j];
+                if self.could_be_other(before, &solution, j) {
+                    continue;
+                }
+                let mut replaced = before.to_vec();
+                replaced[j] = second;
+                if let Some(other) = self.solve(&replaced, None) {
+                    if other[j] == glyph && !self.could_be_other(&replaced, &other, j) {
+                        return FontDecodeError::Conflicting {
+                            glyph,
+                            first,
+                            second,
+                        };
+                    }
+                }
             }
-        };
-        d.swap(last_decreasing, incremented);
-        reverse(d, last_decreasing + 1);
-        Some(())
+            FontDecodeError::Unrecognised { pattern: second }
+        }
+
+        /// Which glyph each pattern on the right of the line is, worked out by searching for the
+        /// wirings that show every pattern on the line as a glyph.
+        pub fn decode(&self, line: &Data) -> Result<Vec<usize>, FontDecodeError> {
+            let mut patterns: Vec<Digit> = Vec::new();
+            for &pattern in line.seen.iter().chain(line.current.iter()) {
+                if !patterns.contains(&pattern) {
+                    patterns.push(pattern);
+                }
+            }
+            let wires = (1u32 << self.segments) - 1;
+            if let Some(&pattern) = patterns.iter().find(|p| p.segments & !wires != 0) {
+                return Err(FontDecodeError::Unrecognised { pattern });
+            }
+            let solution = match self.solve(&patterns, None) {
+                Some(solution) => solution,
+                None => return Err(self.diagnose(&patterns)),
+            };
+
+            line.current
+                .iter()
+                .map(|&pattern| {
+                    let i = patterns.iter().position(|&p| p == pattern).unwrap();
+                    if self.could_be_other(&patterns, &solution, i) {
+                        Err(FontDecodeError::Ambiguous { pattern })
+                    } else {
+                        Ok(solution[i])
+                    }
+                })
+                .collect()
+        }
     }
 
-    /// Returns a permutation of 0..=6, so e.g. 2541063 means
-    /// the input 'a' refers to c, 'b' refers to f, and so on.
-    fn identify(d: &Data) -> [u8; 7] {
-        let mut perm = [0u8; 7];
-        for (i, slot) in perm.iter_mut().enumerate() {
-            *slot = i as u8;
+    /// The four-digit number on the right of the line, worked out from which segments the digits
+    /// on the line share with the 1, 4 and 7.
+    pub fn decode(line: &Data) -> Result<u32, DecodeError> {
+        let font = FONT.map(parse_digit);
+        let table = fingerprint_table();
+        let mut references = [Digit { segments: 0 }; 3];
+        for (reference, &digit) in references.iter_mut().zip(REFERENCES.iter()) {
+            *reference = find_reference(line, &font, digit)?;
         }
 
-        loop {
-            match d
-                .seen
+        let identify = |pattern: Digit| {
+            let fingerprint = fingerprint(pattern, &references);
+            table
                 .iter()
-                .map(|digit| translate(&perm, digit))
-                .find(|translation| translation.is_none())
-            {
-                None => {
-                    return perm;
-                }
-                Some(_) => {
-                    // Some digit was untranslateable
-                }
-            }
-            match next_perm(&mut perm) {
-                None => {
-                    panic!("Expected an answer!");
-                }
-                Some(()) => {
-                    // loop!
+                .find(|(f, _)| *f == fingerprint)
+                .map(|&(_, digit)| digit)
+                .ok_or(DecodeError::Unrecognised { pattern })
+        };
+
+        // Everything seen must be consistent, even if it isn't on the right.
+        let mut patterns: [Option<Digit>; 10] = [None; 10];
+        for &pattern in line.seen.iter().chain(line.current.iter()) {
+            let digit = identify(pattern)?;
+            match patterns[digit as usize] {
+                None => patterns[digit as usize] = Some(pattern),
+                Some(first) if first != pattern => {
+                    return Err(DecodeError::Conflicting {
+                        digit,
+                        first,
+                        second: pattern,
+                    });
                 }
+                Some(_) => {}
             }
         }
+
+        line.current
+            .iter()
+            .try_fold(0, |state, &next| Ok(state * 10 + identify(next)? as u32))
     }
 
     pub fn part_2(data: &[Data]) -> u32 {
         data.iter()
-            .map(|line| {
-                let identification = identify(line);
-                line.current
-                    .map(|i| match translate(&identification, &i) {
-                        None => {
-                            panic!(
-                                "Couldn't translate {:?} using identification {:?}",
-                                i, identification
-                            );
-                        }
-                        Some(digit) => digit,
-                    })
-                    .iter()
-                    .fold(0, |state, next| state * 10 + (*next as u32))
+            .map(|line| match decode(line) {
+                Ok(number) => number,
+                Err(e) => panic!("Couldn't decode {:?}: {:?}", line, e),
             })
             .sum()
     }
@@ -282,180 +511,233 @@ gcafb gcf dcaebfg ecagb gf abcdeg gaef cafbge fdbac fegbdc | fgae cfgab fg bagce
     }
 
     #[test]
-    fn test_next_perm_even() {
-        let mut perm = [0u8; 8];
-        for i in 0..perm.len() {
-            perm[i] = i as u8;
-        }
-
-        next_perm(&mut perm);
-        assert_eq!(equal(&perm, &[0, 1, 2, 3, 4, 5, 7, 6]), true);
-
-        next_perm(&mut perm);
-        assert_eq!(equal(&perm, &[0, 1, 2, 3, 4, 6, 5, 7]), true);
-
-        next_perm(&mut perm);
-        assert_eq!(equal(&perm, &[0, 1, 2, 3, 4, 6, 7, 5]), true);
-
-        next_perm(&mut perm);
-        assert_eq!(equal(&perm, &[0, 1, 2, 3, 4, 7, 5, 6]), true);
+    fn part2_mini_known() {
+        let data = parse(
+            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf",
+        );
 
-        for i in 0..perm.len() {
-            perm[i] = (perm.len() - 1 - i) as u8;
-        }
-        assert_eq!(next_perm(&mut perm), None);
-        assert_eq!(equal(&perm, &[7, 6, 5, 4, 3, 2, 1, 0]), true);
+        assert_eq!(part_2(&data), 5353);
     }
 
     #[test]
-    fn test_next_perm_odd() {
-        let mut perm = [0u8; 7];
-        for i in 0..perm.len() {
-            perm[i] = i as u8 + 1;
+    fn canonical_wiring() {
+        let font = "abcefg cf acdeg acdfg bcdf abdfg abdefg acf abcdefg abcdfg";
+        for (digits, expected) in [
+            ("abcefg cf acdeg acdfg", 123),
+            ("bcdf abdfg abdefg acf", 4567),
+            ("abcdefg abcdfg cf abcefg", 8910),
+        ] {
+            let data = parse(&format!("{} | {}", font, digits));
+            assert_eq!(decode(&data[0]), Ok(expected));
         }
+    }
 
-        next_perm(&mut perm);
-        assert_eq!(equal(&perm, &[1, 2, 3, 4, 5, 7, 6]), true);
-
-        next_perm(&mut perm);
-        assert_eq!(equal(&perm, &[1, 2, 3, 4, 6, 5, 7]), true);
-
-        next_perm(&mut perm);
-        assert_eq!(equal(&perm, &[1, 2, 3, 4, 6, 7, 5]), true);
+    // Cross the wires: segment i is shown on segment wiring[i].
+    fn scramble(glyph: Digit, wiring: &[u32]) -> String {
+        let segments = wiring
+            .iter()
+            .enumerate()
+            .filter(|&(i, _)| glyph.segments & (1 << i) != 0)
+            .fold(0, |acc, (_, &to)| acc | (1 << to));
+        Digit { segments }.to_string()
+    }
 
-        next_perm(&mut perm);
-        assert_eq!(equal(&perm, &[1, 2, 3, 4, 7, 5, 6]), true);
+    fn check_font(font: &SegmentFont, wiring: &[u32]) {
+        let glyphs = font.glyphs();
+        let seen: Vec<String> = glyphs.iter().rev().map(|&g| scramble(g, wiring)).collect();
+        let shown = [3, 0, glyphs.len() - 1, 5];
+        let current: Vec<String> = shown.iter().map(|&i| scramble(glyphs[i], wiring)).collect();
+        let data = parse(&format!("{} | {}", seen.join(" "), current.join(" ")));
+        assert_eq!(font.decode(&data[0]), Ok(shown.to_vec()));
+    }
 
-        for (i, &value) in [0, 1, 2, 5, 3, 4, 6].iter().enumerate() {
-            perm[i] = value;
+    #[test]
+    fn other_fonts() {
+        let sevens: [&[u32]; 3] = [
+            &[0, 1, 2, 3, 4, 5, 6],
+            &[6, 5, 4, 3, 2, 1, 0],
+            &[2, 5, 4, 1, 0, 6, 3],
+        ];
+        for wiring in sevens {
+            check_font(&SegmentFont::decimal(), wiring);
+            check_font(&SegmentFont::hexadecimal(), wiring);
         }
-        next_perm(&mut perm);
-        assert_eq!(equal(&perm, &[0, 1, 2, 5, 3, 6, 4]), true);
-
-        next_perm(&mut perm);
-        assert_eq!(equal(&perm, &[0, 1, 2, 5, 4, 3, 6]), true);
-
-        next_perm(&mut perm);
-        assert_eq!(equal(&perm, &[0, 1, 2, 5, 4, 6, 3]), true);
 
-        next_perm(&mut perm);
-        assert_eq!(equal(&perm, &[0, 1, 2, 5, 6, 3, 4]), true);
-
-        next_perm(&mut perm);
-        assert_eq!(equal(&perm, &[0, 1, 2, 5, 6, 4, 3]), true);
-
-        next_perm(&mut perm);
-        assert_eq!(equal(&perm, &[0, 1, 2, 6, 3, 4, 5]), true);
+        // Some letters on a fourteen-segment display: the outside is a to f clockwise from the
+        // top, g and h are the middle, and i to n are the inner diagonals and verticals.
+        let letters = SegmentFont::new(
+            14,
+            &[
+                "abcefgh", "abcdhjm", "adef", "abcdjm", "adefg", "aefg", "acdefh", "bcefgh",
+                "adjm", "bcde", "efgkn", "def", "bcefik", "bcefin", "abcdef", "iknl", "adkl",
+            ],
+        )
+        .unwrap();
+        let fourteens: [&[u32]; 2] = [
+            &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13],
+            &[9, 3, 13, 0, 7, 12, 1, 4, 10, 6, 2, 11, 5, 8],
+        ];
+        for wiring in fourteens {
+            check_font(&letters, wiring);
+        }
 
-        for i in 0..perm.len() {
-            perm[i] = (perm.len() - i) as u8;
+        // All the same size, and the same overlaps between them: only which segments they
+        // share, not how many, tells them apart.
+        let pairs = SegmentFont::new(6, &["ab", "ac", "ad", "bc", "be", "df"]).unwrap();
+        let sixes: [&[u32]; 2] = [&[0, 1, 2, 3, 4, 5], &[3, 5, 0, 1, 4, 2]];
+        for wiring in sixes {
+            check_font(&pairs, wiring);
         }
-        assert_eq!(next_perm(&mut perm), None);
-        assert_eq!(equal(&perm, &[7, 6, 5, 4, 3, 2, 1]), true);
     }
 
     #[test]
-    fn part2_mini_known() {
-        let data = parse(
-            "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab | cdfeb fcadb cdfeb cdbaf",
+    fn font_errors() {
+        // Mirror images of each other, so there's no telling which is which.
+        assert_eq!(
+            SegmentFont::new(2, &["a", "b"]).unwrap_err(),
+            FontError::NotIdentifiable { glyphs: vec![0, 1] }
+        );
+        // Both 2 and 5 are five segments sharing one with the 1 and two with the 7, and nothing
+        // else is seen to separate them.
+        assert_eq!(
+            SegmentFont::new(7, &["cf", "acf", "acdeg", "abdfg"]).unwrap_err(),
+            FontError::NotIdentifiable { glyphs: vec![2, 3] }
+        );
+        assert_eq!(
+            SegmentFont::new(7, &["cf", "cf"]).unwrap_err(),
+            FontError::DuplicateGlyph {
+                first: 0,
+                second: 1
+            }
+        );
+        assert_eq!(
+            SegmentFont::new(3, &["ad"]).unwrap_err(),
+            FontError::UnknownSegment {
+                glyph: 0,
+                segment: 'd'
+            }
+        );
+        assert_eq!(
+            SegmentFont::new(27, &[]).unwrap_err(),
+            FontError::TooManySegments { segments: 27 }
         );
-
-        assert_eq!(part_2(&data), 5353);
     }
 
     #[test]
-    fn translations() {
-        let identification = [0, 1, 2, 3, 4, 5, 6];
-        assert_eq!(
-            translate(
-                &identification,
-                &Digit {
-                    segments: [true, true, true, false, true, true, true]
-                }
-            ),
-            Some(0)
-        );
+    fn decode_errors() {
+        let mini = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab";
+        let line = |s: &str| parse(s).remove(0);
+
+        // Without the 4, the 0, 6 and 9 can't be told apart.
+        let no_four = line("acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb cagedb ab | ab ab ab ab");
         assert_eq!(
-            translate(
-                &identification,
-                &Digit {
-                    segments: [false, false, true, false, false, true, false]
-                }
-            ),
-            Some(1)
+            decode(&no_four),
+            Err(DecodeError::MissingReference { digit: 4 })
         );
+
+        let two_ones = line(&format!("{} bc | ab ab ab ab", mini));
         assert_eq!(
-            translate(
-                &identification,
-                &Digit {
-                    segments: [true, false, true, true, true, false, true]
-                }
-            ),
-            Some(2)
+            decode(&two_ones),
+            Err(DecodeError::Conflicting {
+                digit: 1,
+                first: parse_digit("ab"),
+                second: parse_digit("bc"),
+            })
         );
+
+        let nonsense = line(&format!("{} | ab ab ab bcg", mini));
         assert_eq!(
-            translate(
-                &identification,
-                &Digit {
-                    segments: [true, false, true, true, false, true, true]
-                }
-            ),
-            Some(3)
+            decode(&nonsense),
+            Err(DecodeError::Unrecognised {
+                pattern: parse_digit("bcg")
+            })
         );
+
+        // Looks like a 3, but isn't the 3 that was seen.
+        let wrong_three = line(&format!("{} | ab ab ab abcde", mini));
         assert_eq!(
-            translate(
-                &identification,
-                &Digit {
-                    segments: [false, true, true, true, false, true, false]
-                }
-            ),
-            Some(4)
+            decode(&wrong_three),
+            Err(DecodeError::Conflicting {
+                digit: 3,
+                first: parse_digit("fbcad"),
+                second: parse_digit("abcde"),
+            })
         );
+
+        // Segment order doesn't matter.
+        let shuffled = line(&format!("{} | ba fbcad cdbaf dab", mini));
+        assert_eq!(decode(&shuffled), Ok(1337));
+    }
+
+    #[test]
+    fn font_decode_errors() {
+        let mini = "acedgfb cdfbe gcdfa fbcad dab cefabd cdfgeb eafb cagedb ab";
+        let line = |s: &str| parse(s).remove(0);
+        let font = SegmentFont::decimal();
+        let decode = |line: &Data| font.decode(line);
+
+        // Without more to go on, a 2 and a 5 look the same.
+        let too_little = line("ab dab | cdfbe ab ab ab");
         assert_eq!(
-            translate(
-                &identification,
-                &Digit {
-                    segments: [true, true, false, true, false, true, true]
-                }
-            ),
-            Some(5)
+            decode(&too_little),
+            Err(FontDecodeError::Ambiguous {
+                pattern: parse_digit("cdfbe")
+            })
         );
+
+        // The rest of the line says which of these is the 1, so the other isn't anything.
+        let two_ones = line(&format!("{} bc | ab ab ab ab", mini));
         assert_eq!(
-            translate(
-                &identification,
-                &Digit {
-                    segments: [true, true, false, true, true, true, true]
-                }
-            ),
-            Some(6)
+            decode(&two_ones),
+            Err(FontDecodeError::Unrecognised {
+                pattern: parse_digit("bc")
+            })
         );
+
+        let nonsense = line(&format!("{} | ab ab ab bcg", mini));
         assert_eq!(
-            translate(
-                &identification,
-                &Digit {
-                    segments: [true, false, true, false, false, true, false]
-                }
-            ),
-            Some(7)
+            decode(&nonsense),
+            Err(FontDecodeError::Unrecognised {
+                pattern: parse_digit("bcg")
+            })
         );
+
+        // Looks like a 3, but doesn't fit with the 3 that was seen.
+        let wrong_three = line(&format!("{} | ab ab ab abcde", mini));
         assert_eq!(
-            translate(
-                &identification,
-                &Digit {
-                    segments: [true, true, true, true, true, true, true]
-                }
-            ),
-            Some(8)
+            decode(&wrong_three),
+            Err(FontDecodeError::Unrecognised {
+                pattern: parse_digit("abcde")
+            })
         );
+
+        // With nothing else to go on, either of these could be the 1.
+        let lone_ones = line("ab bc | ab ab ab ab");
         assert_eq!(
-            translate(
-                &identification,
-                &Digit {
-                    segments: [true, true, true, true, false, true, true]
-                }
-            ),
-            Some(9)
+            decode(&lone_ones),
+            Err(FontDecodeError::Conflicting {
+                glyph: 1,
+                first: parse_digit("ab"),
+                second: parse_digit("bc"),
+            })
         );
+
+        // Segment order doesn't matter.
+        let shuffled = line(&format!("{} | ba fbcad cdbaf dab", mini));
+        assert_eq!(decode(&shuffled), Ok(vec![1, 3, 3, 7]));
+    }
+
+    // The fingerprint decoder and the general font agree wherever the puzzle can be solved.
+    #[test]
+    fn font_agrees_with_fingerprints() {
+        let font = SegmentFont::decimal();
+        for line in input().iter().chain(parse(TEST_INPUT).iter()) {
+            let digits = font.decode(line).unwrap();
+            let number = digits
+                .iter()
+                .fold(0, |state, &next| state * 10 + next as u32);
+            assert_eq!(decode(line), Ok(number));
+        }
     }
 
     #[test]
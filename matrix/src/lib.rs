@@ -0,0 +1,171 @@
+pub mod matrix {
+
+    use std::num::NonZeroU64;
+
+    /// How to do the arithmetic when the counts may be too large for a `u64`.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum Arithmetic {
+        /// Exact `u128` counts, failing if they overflow.
+        Checked,
+        /// Counts modulo the given number.
+        Modulo(NonZeroU64),
+    }
+
+    impl Arithmetic {
+        // The representative of `a`: itself, or its remainder modulo the modulus.
+        fn reduce(self, a: u128) -> u128 {
+            match self {
+                Arithmetic::Checked => a,
+                Arithmetic::Modulo(m) => a % m.get() as u128,
+            }
+        }
+
+        /// The sum, or `None` if it overflows under `Checked`. Modulo a number, the arguments
+        /// are reduced first, so it can't overflow.
+        pub fn add(self, a: u128, b: u128) -> Option<u128> {
+            match self {
+                Arithmetic::Checked => a.checked_add(b),
+                Arithmetic::Modulo(_) => Some(self.reduce(self.reduce(a) + self.reduce(b))),
+            }
+        }
+
+        /// The product, or `None` if it overflows under `Checked`. Modulo a number, the
+        /// arguments are reduced first, and since the modulus fits in a `u64` their product
+        /// fits in a `u128`.
+        pub fn mul(self, a: u128, b: u128) -> Option<u128> {
+            match self {
+                Arithmetic::Checked => a.checked_mul(b),
+                Arithmetic::Modulo(_) => Some(self.reduce(self.reduce(a) * self.reduce(b))),
+            }
+        }
+    }
+
+    /// A square matrix, stored row by row.
+    #[derive(Debug, Clone)]
+    pub struct Matrix {
+        size: usize,
+        elts: Vec<u128>,
+    }
+
+    impl Matrix {
+        /// Panics unless there are `size * size` elements.
+        pub fn make(size: usize, elts: Vec<u128>) -> Matrix {
+            if elts.len() != size * size {
+                panic!(
+                    "Expected {} elements for a matrix of size {}, got {}",
+                    size * size,
+                    size,
+                    elts.len()
+                );
+            }
+            Matrix { size, elts }
+        }
+
+        /// The product of the matrices, or `None` if anything overflows under
+        /// `Arithmetic::Checked`.
+        pub fn mul(&self, other: &Matrix, arithmetic: Arithmetic) -> Option<Matrix> {
+            let size = self.size;
+            let mut elts = vec![0; size * size];
+            for row in 0..size {
+                for k in 0..size {
+                    let a = self.elts[row * size + k];
+                    if a == 0 {
+                        continue;
+                    }
+                    for col in 0..size {
+                        let b = other.elts[k * size + col];
+                        let product = arithmetic.mul(a, b)?;
+                        elts[row * size + col] = arithmetic.add(elts[row * size + col], product)?;
+                    }
+                }
+            }
+            Some(Matrix { size, elts })
+        }
+
+        /// The matrix applied to the column vector `v`, or `None` if anything overflows under
+        /// `Arithmetic::Checked`.
+        pub fn apply(&self, v: &[u128], arithmetic: Arithmetic) -> Option<Vec<u128>> {
+            let mut result = vec![0; self.size];
+            for (row, entry) in result.iter_mut().enumerate() {
+                for (col, &x) in v.iter().enumerate() {
+                    let product = arithmetic.mul(self.elts[row * self.size + col], x)?;
+                    *entry = arithmetic.add(*entry, product)?;
+                }
+            }
+            Some(result)
+        }
+
+        /// The matrix raised to the power `exponent`, applied to `v`, by repeated squaring so
+        /// the cost grows with the logarithm of `exponent`.
+        /// Returns `None` if anything overflows under `Arithmetic::Checked`.
+        pub fn apply_pow(
+            &self,
+            v: &[u128],
+            exponent: u64,
+            arithmetic: Arithmetic,
+        ) -> Option<Vec<u128>> {
+            let mut result: Vec<u128> = v.iter().map(|&x| arithmetic.reduce(x)).collect();
+            let mut power = self.clone();
+            let mut exponent = exponent;
+            while exponent > 0 {
+                if exponent % 2 == 1 {
+                    result = power.apply(&result, arithmetic)?;
+                }
+                exponent /= 2;
+                if exponent > 0 {
+                    power = power.mul(&power, arithmetic)?;
+                }
+            }
+            Some(result)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matrix::*;
+    use std::num::NonZeroU64;
+
+    #[test]
+    fn fibonacci() {
+        let step = Matrix::make(2, vec![1, 1, 1, 0]);
+        assert_eq!(
+            step.apply_pow(&[1, 0], 10, Arithmetic::Checked),
+            Some(vec![89, 55])
+        );
+        assert_eq!(
+            step.apply_pow(&[1, 0], 0, Arithmetic::Checked),
+            Some(vec![1, 0])
+        );
+        // F(186) is the largest Fibonacci number that fits in a u128.
+        assert!(step.apply_pow(&[1, 0], 185, Arithmetic::Checked).is_some());
+        assert_eq!(step.apply_pow(&[1, 0], 190, Arithmetic::Checked), None);
+
+        let modulus = NonZeroU64::new(1_000_000_007).unwrap();
+        let exact = step.apply_pow(&[1, 0], 150, Arithmetic::Checked).unwrap();
+        let modular = step
+            .apply_pow(&[1, 0], 150, Arithmetic::Modulo(modulus))
+            .unwrap();
+        for (exact, modular) in exact.into_iter().zip(modular) {
+            assert_eq!(modular, exact % modulus.get() as u128);
+        }
+    }
+
+    #[test]
+    fn unreduced_entries() {
+        // Entries and vectors of any size are reduced before they're combined.
+        let m = 1_000_000_007u128;
+        let modulus = Arithmetic::Modulo(NonZeroU64::new(m as u64).unwrap());
+        let big = u128::MAX - 5;
+        let step = Matrix::make(2, vec![big, 1, 1, 0]);
+        let reduced = Matrix::make(2, vec![big % m, 1, 1, 0]);
+        for exponent in [0, 1, 2, 17] {
+            assert_eq!(
+                step.apply_pow(&[big, big], exponent, modulus),
+                reduced.apply_pow(&[big % m, big % m], exponent, modulus)
+            );
+        }
+        assert_eq!(modulus.add(big, big), Some((big % m) * 2 % m));
+        assert_eq!(modulus.mul(big, big), Some((big % m) * (big % m) % m));
+    }
+}